@@ -0,0 +1,9006 @@
+// Bevy's queries spell out what they're after in their types.
+#![allow(clippy::type_complexity)]
+
+use bevy::{
+    app::AppExit,
+    diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
+    ecs::system::{EntityCommands, SystemParam},
+    input::mouse::MouseWheel,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    time::Stopwatch,
+    window::WindowMode,
+};
+use rand::{prelude::*, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashSet, VecDeque},
+    io,
+    marker::PhantomData,
+    time::Duration,
+};
+
+const SNAKE_HEAD_MARK_COLOR: Color = Color::rgb(0.04, 0.04, 0.04);
+const WALL_COLOR: Color = Color::rgb(0.55, 0.25, 0.2);
+const ENEMY_COLOR: Color = Color::rgb(0.95, 0.45, 0.1);
+const GRID_COLOR: Color = Color::rgb(0.12, 0.12, 0.12);
+const BORDER_COLOR: Color = Color::rgb(0.8, 0.15, 0.15);
+const SHRUNK_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.7);
+const MASKED_COLOR: Color = Color::rgba(0.55, 0.25, 0.2, 0.35);
+const SHRINK_WARNING_COLOR: Color = Color::rgba(0.8, 0.15, 0.15, 0.3);
+const LENGTH_BAR_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.6);
+const LENGTH_BAR_TRACK_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.1);
+const PORTAL_COLOR: Color = Color::rgb(0.55, 0.3, 0.95);
+const PAUSE_OVERLAY_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.6);
+// What the background drifts towards as the score goes up, and how far it gets
+// there by the time the score reaches the full amount.
+const SCORE_BACKGROUND_COLOR: Color = Color::rgb(0.1, 0.03, 0.18);
+const SCORE_BACKGROUND_MIX: f32 = 0.6;
+const SCORE_BACKGROUND_FULL_SCORE: f32 = 100.0;
+const PAUSE_OPTION_COLOR: Color = Color::rgb(0.5, 0.5, 0.5);
+const GHOST_COLOR: Color = Color::rgba(0.6, 0.8, 1.0, 0.25);
+const WRAP_MARKER_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.25);
+const WRAP_FLASH_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.6);
+const DEATH_FLASH_COLOR: Color = Color::rgb(0.9, 0.1, 0.1);
+
+// Sprites are drawn in order of z, which `position_scaling` leaves alone. The
+// camera can't see anything below 0.
+const BACKGROUND_Z: f32 = 0.0;
+const MARKER_Z: f32 = 0.5;
+const FOREGROUND_Z: f32 = 1.0;
+
+// The movement tick starts at the difficulty's base interval and shortens by
+// the step for every segment the snake grows, down to the minimum.
+const MOVEMENT_INTERVAL_STEP: f32 = 0.005;
+const MIN_MOVEMENT_INTERVAL: f32 = 0.060;
+
+// Every time the food spawner runs there's this chance of also spawning bonus
+// food, if there isn't any already.
+const BONUS_FOOD_CHANCE: f32 = 0.1;
+const BONUS_FOOD_LIFETIME: f32 = 5.0;
+const BONUS_FOOD_POINTS: usize = 5;
+// Same for poison, which shrinks the snake rather than growing it.
+const POISON_FOOD_CHANCE: f32 = 0.05;
+const POISON_FOOD_PENALTY: usize = 2;
+// And for slow-mo food, which stretches out the movement tick by the factor for
+// a while, easing back to normal speed over the last stretch of it.
+const SLOW_FOOD_CHANCE: f32 = 0.05;
+const SLOW_MO_SECONDS: f32 = 4.0;
+const SLOW_MO_FADE_SECONDS: f32 = 1.0;
+const SLOW_MO_FACTOR: f32 = 1.6;
+// And for freeze food, which holds every snake still for a moment while turns
+// can still be lined up.
+const FREEZE_FOOD_CHANCE: f32 = 0.04;
+const FREEZE_SECONDS: f32 = 1.5;
+// And for magnet food, which pulls food in towards the head for a while.
+const MAGNET_FOOD_CHANCE: f32 = 0.04;
+const MAGNET_SECONDS: f32 = 4.0;
+// And for big food, which takes up a square of tiles this many across.
+const BIG_FOOD_CHANCE: f32 = 0.03;
+const BIG_FOOD_SIZE: i32 = 2;
+const BIG_FOOD_POINTS: usize = 10;
+const BIG_FOOD_GROWTH: usize = 3;
+// And for a pair of portals, which last until they've been used a few times or
+// run out of time.
+const PORTAL_CHANCE: f32 = 0.05;
+const PORTAL_LIFETIME: f32 = 10.0;
+const PORTAL_USES: u32 = 3;
+// The roaming enemy turns off on its own every so often, and never shows up
+// any closer than this to a head.
+const ENEMY_TURN_CHANCE: f32 = 0.3;
+const ENEMY_SPAWN_DISTANCE: i32 = 4;
+const FOOD_SPAWN_INTERVAL: f32 = 1.0;
+// In the shrinking arena, the edges close in by a tile every so often, with a
+// warning beforehand, until there's only so much room left.
+const SHRINK_INTERVAL: f32 = 15.0;
+const SHRINK_WARNING: f32 = 3.0;
+const MIN_SHRUNK_SIZE: u32 = 4;
+// The panic button can only be pressed this often, going by the snake's clock.
+const PANIC_COOLDOWN_SECONDS: f32 = 60.0;
+// How far the arena can be resized in sandbox mode.
+const MIN_ARENA_SIZE: u32 = 4;
+const MAX_ARENA_SIZE: u32 = 40;
+// Eating again within the window bumps up the multiplier on points, to a cap.
+const COMBO_WINDOW: f32 = 2.0;
+const MAX_COMBO: u32 = 8;
+const DEFAULT_SEED: u64 = 0x5EED;
+const SEED_VAR: &str = "SNAKE_SEED";
+
+// Where each player's head starts, with the rest of the body below it.
+const SNAKE_STARTS: [(i32, i32); 2] = [(3, 3), (8, 3)];
+
+// Obstacle tiles that kill the snake on contact.
+const WALL_POSITIONS: &[(i32, i32)] = &[(6, 5), (6, 6), (6, 7), (7, 7)];
+
+// How many turns can be queued up between movement ticks.
+const INPUT_BUFFER_CAPACITY: usize = 2;
+// However big the gap between segments, they never shrink past this much of a
+// tile, and the head's always a bit bigger than the rest of the body.
+const MIN_SEGMENT_SIZE: f32 = 0.4;
+const HEAD_EXTRA_SIZE: f32 = 0.15;
+
+// How many pixels across the shapes food is drawn with in colorblind mode are.
+const FOOD_SHAPE_PIXELS: u32 = 32;
+
+const STARTING_LIVES: u32 = 3;
+
+const COUNTDOWN_SECONDS: f32 = 3.0;
+
+const WRAP_FLASH_SECONDS: f32 = 0.3;
+
+const TRAIL_SECONDS: f32 = 0.3;
+const TRAIL_ALPHA: f32 = 0.4;
+
+const DEATH_FLASH_SECONDS: f32 = 0.5;
+const DEATH_FLASHES: u32 = 2;
+
+// Particles fly out from eaten food at this speed, in tiles per second.
+const PARTICLE_COUNT: usize = 8;
+const PARTICLE_SPEED: f32 = 3.0;
+const PARTICLE_LIFETIME: f32 = 0.4;
+
+// Points float up off eaten food this many tiles as they fade.
+const SCORE_POPUP_SECONDS: f32 = 0.6;
+const SCORE_POPUP_RISE: f32 = 1.0;
+
+const ACHIEVEMENT_TOAST_SECONDS: f32 = 3.0;
+const NO_WRAP_STREAK: u32 = 10;
+const LONG_SNAKE_LENGTH: usize = 20;
+
+const SCREEN_SHAKE_SECONDS: f32 = 0.3;
+// In pixels.
+const SCREEN_SHAKE_INTENSITY: f32 = 8.0;
+
+// Each step of zoom magnifies the board by this much, up to a limit.
+const ZOOM_STEP: f32 = 1.25;
+const MAX_ZOOM_STEPS: i32 = 6;
+
+const GAMEPAD_STICK_DEADZONE: f32 = 0.5;
+// In logical pixels, so taps and clicks don't count as swipes.
+const SWIPE_MIN_DISTANCE: f32 = 30.0;
+
+const WINDOW_TITLE: &str = "Snake";
+
+const FONT_PATH: &str = "press-start.ttf";
+const EAT_SOUND_PATH: &str = "sounds/eat.wav";
+const DEATH_SOUND_PATH: &str = "sounds/death.wav";
+// Optional, the game falls back to plain squares without them.
+const SNAKE_HEAD_SPRITE_PATH: &str = "sprites/snake-head.png";
+const SNAKE_BODY_SPRITE_PATH: &str = "sprites/snake-body.png";
+const HIGH_SCORE_PATH: &str = "highscore.dat";
+const REPLAY_PATH: &str = "best-run.ron";
+const SETTINGS_PATH: &str = "settings.ron";
+const SNAPSHOT_PATH: &str = "snapshot.ron";
+const ACHIEVEMENTS_PATH: &str = "achievements.ron";
+const DAILY_BEST_PATH: &str = "daily.ron";
+// Bumped whenever `GameSnapshot` changes, so older snapshots are turned away
+// rather than loaded wrong.
+const SNAPSHOT_VERSION: u32 = 2;
+
+/// The whole game, ready to be added to an app alongside `DefaultPlugins`.
+pub struct SnakePlugin {
+    /// What to resize the primary window to on startup, or `None` to leave it
+    /// as it is.
+    pub window_size: Option<Vec2>,
+    pub theme: Theme,
+    /// Whether the high score and settings are loaded from and saved to disk.
+    pub persist: bool,
+    /// How long, in seconds, the snakes that died flash for before the run
+    /// carries on or ends. Zero skips straight past it.
+    pub death_flash: f32,
+}
+
+impl Default for SnakePlugin {
+    fn default() -> Self {
+        Self {
+            window_size: Some(Vec2::new(500.0, 500.0)),
+            theme: Theme::default(),
+            persist: true,
+            death_flash: DEATH_FLASH_SECONDS,
+        }
+    }
+}
+
+impl Plugin for SnakePlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(size) = self.window_size {
+            app.add_startup_system(move |mut windows: ResMut<Windows>| {
+                if let Some(window) = windows.get_primary_mut() {
+                    window.set_resolution(size.x, size.y);
+                }
+            });
+        }
+
+        app.insert_resource(self.theme)
+            .insert_resource(ClearColor(self.theme.palette().background))
+            .add_system(cycle_theme)
+            .add_system(apply_theme.after(cycle_theme))
+            .insert_resource(ScoreBackground::default())
+            .add_system(toggle_score_background)
+            .add_system(
+                score_background
+                    .after(toggle_score_background)
+                    .after(cycle_theme)
+                    .after(snake_eating),
+            )
+            .insert_resource(RainbowSkin::default())
+            .add_system(toggle_rainbow_skin)
+            .add_system(rainbow_skin.after(toggle_rainbow_skin).after(apply_theme))
+            .insert_resource(ColorblindMode::default())
+            .add_startup_system(make_food_shapes)
+            .add_system(toggle_colorblind_mode)
+            .add_system(
+                apply_colorblind_mode
+                    .after(toggle_colorblind_mode)
+                    .after(apply_theme),
+            )
+            .insert_resource(ArenaSize::default())
+            .insert_resource(EffectiveArena::from(&ArenaSize::default()))
+            .insert_resource(ArenaShrink::default())
+            .add_system(shrink_arena.after(snake_movement))
+            .add_system(shrink_overlay.after(shrink_arena))
+            .insert_resource(StartLength::default())
+            .insert_resource(SegmentGap::default())
+            .add_system(apply_segment_gap)
+            .insert_resource(GameState::default())
+            .insert_resource(GameMode::default())
+            .insert_resource(Volume::default())
+            .add_startup_system(setup)
+            .insert_resource(GridVisible::default())
+            .add_system(spawn_grid)
+            .add_startup_system(spawn_walls)
+            .insert_resource(ArenaShape::default())
+            .insert_resource(ArenaMask::default())
+            .add_system(cycle_arena_shape.before(menu))
+            .add_system(
+                update_arena_mask
+                    .after(menu)
+                    .after(resize_arena)
+                    .before(snake_movement)
+                    .before(food_spawner),
+            )
+            .add_system(spawn_border)
+            .insert_resource(Sandbox::default())
+            .add_system(toggle_sandbox)
+            .add_system(
+                resize_arena
+                    .after(toggle_sandbox)
+                    .before(spawn_grid)
+                    .before(spawn_border),
+            )
+            .add_system(border_visibility)
+            .add_system(toggle_grid)
+            .add_system(toggle_fullscreen)
+            .add_plugin(FrameTimeDiagnosticsPlugin)
+            .insert_resource(ShowFps::default())
+            .add_system(fps_counter)
+            .insert_resource(LengthBar::default())
+            .add_system(length_bar.after(snake_growth))
+            .insert_resource(DebugOverlay::default())
+            .add_system(debug_overlay.after(snake_movement))
+            .insert_resource(HighScore::default())
+            .add_startup_system(setup_menu)
+            .add_system(menu.before(snake_movement_input))
+            .add_system(demo_snake.after(menu))
+            .add_system(ai_steering.after(demo_snake).before(snake_movement))
+            .insert_resource(Countdown::default())
+            .add_system(countdown.before(snake_movement))
+            .add_system_set_to_stage(
+                CoreStage::PostUpdate,
+                SystemSet::new()
+                    .with_system(size_scaling)
+                    .with_system(position_scaling)
+                    .with_system(head_rotation),
+            )
+            .insert_resource(SmoothMovement::default())
+            .insert_resource(TickProgress::default())
+            .add_system(toggle_smooth_movement)
+            .add_system(track_previous_positions.after(snake_movement))
+            .add_system(tick_progress.after(snake_movement))
+            .insert_resource(KeyBindings::default())
+            .add_system(snake_movement_input.before(snake_movement))
+            .insert_resource(PreTick::default())
+            .insert_resource(DiagonalPolicy::default())
+            .add_system(toggle_pre_tick)
+            .add_system_to_stage(CoreStage::PostUpdate, commit_direction)
+            .add_system(swipe_input.before(snake_movement))
+            .insert_resource(WrapMode::default())
+            .insert_resource(WallGrace::default())
+            .add_system(toggle_wrap_mode.before(snake_movement))
+            .add_event::<GrowthEvent>()
+            .insert_resource(GrowthPerFood::default())
+            .add_event::<WrapEvent>()
+            .add_system(wrap_markers.after(snake_movement))
+            .add_system(wrap_flash.after(snake_movement))
+            .insert_resource(MovementTimer::default())
+            .insert_resource(SpeedRamp::default())
+            .insert_resource(Difficulty::default())
+            .insert_resource(SlowMo::default())
+            .insert_resource(FreezeTimer::default())
+            .insert_resource(Magnet::default())
+            .add_system(magnet.after(snake_movement).before(snake_eating))
+            .add_system(
+                freeze
+                    .after(start_recording)
+                    .after(countdown)
+                    .before(snake_movement),
+            )
+            .add_system(movement_speed.after(snake_movement).after(start_recording))
+            .add_system(cycle_difficulty)
+            .insert_resource(Invincible::default())
+            .add_system(toggle_practice)
+            .insert_resource(RoamingEnemy::default())
+            .add_system(toggle_roaming_enemy)
+            .add_system(toggle_speed_ramp)
+            .add_system(roaming_enemy.after(snake_movement).after(snake_eating))
+            .add_system_set(
+                SystemSet::new()
+                    .with_system(snake_movement)
+                    .with_system(snake_eating.after(snake_movement))
+                    .with_system(snake_growth.after(snake_movement)),
+            )
+            .insert_resource(MovementTick::default())
+            .insert_resource(FoodRespawn::default())
+            .insert_resource(FoodTimer::default())
+            .insert_resource(FoodCount::default())
+            .insert_resource(GameRng::from_env())
+            .add_system(food_spawner.after(snake_eating))
+            .add_system(bonus_food_expiry.after(snake_movement))
+            .add_system(portal_expiry.after(snake_movement))
+            .insert_resource(ParticlesEnabled::default())
+            .add_system(toggle_particles)
+            .add_system(particles.after(snake_eating))
+            .add_system(score_popups.after(snake_eating))
+            .insert_resource(TrailEnabled::default())
+            .add_system(toggle_trail)
+            .add_system(spawn_trail.after(snake_movement))
+            .add_system(trail.after(spawn_trail))
+            .insert_resource(WrapGhosts::default())
+            .add_system(toggle_wrap_ghosts)
+            .add_system(
+                wrap_ghosts
+                    .after(track_previous_positions)
+                    .after(tick_progress)
+                    .after(death_flash),
+            )
+            .insert_resource(ConnectedBody::default())
+            .add_system(toggle_connected_body)
+            .add_system(
+                connected_body
+                    .after(track_previous_positions)
+                    .after(tick_progress)
+                    .after(death_flash),
+            )
+            .insert_resource(Replay::default())
+            .insert_resource(ReplayMode::default())
+            .insert_resource(PlayerOptions::default())
+            .add_event::<NewRunEvent>()
+            .insert_non_send_resource(Ghost::default())
+            .add_system(ghost.after(snake_movement).after(start_recording))
+            .add_system(
+                start_recording
+                    .after(menu)
+                    .after(restart)
+                    .before(snake_movement),
+            )
+            .add_system(
+                replay_playback
+                    .after(start_recording)
+                    .before(snake_movement),
+            )
+            .insert_resource(PanicCooldown::default())
+            .add_system(
+                panic_button
+                    .after(start_recording)
+                    .after(freeze)
+                    .before(snake_movement)
+                    .before(pause_menu),
+            )
+            .add_system(panic_text.after(panic_button))
+            .insert_resource(Score::default())
+            .insert_resource(Combo::default())
+            .add_system(combo_decay.after(snake_movement))
+            .add_system(score_text)
+            .add_system(window_title)
+            .insert_resource(Lives::default())
+            .add_system(save_snapshot.after(snake_movement))
+            .add_system(load_snapshot.before(snake_movement_input))
+            .add_system(lives_text)
+            .insert_resource(PlayTime::default())
+            .add_system(play_time.after(start_recording))
+            .add_system(play_time_text.after(play_time))
+            .add_event::<GameOverEvent>()
+            .add_system(game_over.after(snake_movement))
+            .insert_resource(DeathFlash::new(self.death_flash))
+            .add_system(death_flash.after(game_over).after(rainbow_skin))
+            .insert_resource(ScreenShake::default())
+            .add_system(toggle_screen_shake)
+            .add_system(
+                screen_shake
+                    .after(snake_movement)
+                    .after(snake_eating)
+                    .after(camera_zoom),
+            )
+            .insert_resource(CameraZoom::default())
+            .add_system(camera_zoom.after(snake_movement).after(resize_arena))
+            .add_event::<RestartEvent>()
+            .add_system(restart.before(snake_movement_input))
+            .add_system(pause_menu.after(snake_movement_input))
+            .add_event::<GameWonEvent>()
+            .add_system(game_won.before(game_over))
+            .insert_resource(Achievements::default())
+            .add_system(achievements.after(snake_eating).after(snake_growth))
+            .add_system(achievement_toasts)
+            .insert_resource(DailyChallenge::default())
+            .add_system(toggle_daily_challenge.before(menu))
+            .add_system(daily_challenge_text.after(menu).after(game_over))
+            .insert_resource(GameStats::default())
+            .add_system_to_stage(CoreStage::PostUpdate, game_stats);
+
+        if self.persist {
+            app.add_startup_system(load_high_score)
+                .add_startup_system(load_settings)
+                .add_system(save_settings)
+                .add_startup_system(load_achievements)
+                .add_system(save_achievements.after(achievements))
+                .add_startup_system(load_daily_best);
+        }
+    }
+}
+
+/// A set of colors for the board and everything on it.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    #[default]
+    Classic,
+    Neon,
+    Mono,
+}
+
+struct Palette {
+    background: Color,
+    // Indexed by player.
+    snake_heads: [Color; 2],
+    snake_segments: [Color; 2],
+    food: Color,
+    bonus_food: Color,
+    poison_food: Color,
+    slow_food: Color,
+    freeze_food: Color,
+    magnet_food: Color,
+    big_food: Color,
+}
+
+impl Palette {
+    // The color of the segment at `index` along a player's snake, counting
+    // the head as 0.
+    fn snake_segment(&self, player: usize, index: usize) -> Color {
+        if index == 0 {
+            self.snake_heads[player]
+        } else {
+            self.snake_segments[player]
+        }
+    }
+
+    fn food_color(&self, kind: FoodKind) -> Color {
+        match kind {
+            FoodKind::Normal => self.food,
+            FoodKind::Bonus => self.bonus_food,
+            FoodKind::Poison => self.poison_food,
+            FoodKind::Slow => self.slow_food,
+            FoodKind::Big => self.big_food,
+            FoodKind::Freeze => self.freeze_food,
+            FoodKind::Magnet => self.magnet_food,
+        }
+    }
+
+    // The same palette with the food swapped for colors that stay apart with
+    // the common kinds of color blindness.
+    fn colorblind(self) -> Self {
+        Self {
+            food: Color::rgb(0.35, 0.7, 0.9),
+            bonus_food: Color::rgb(0.95, 0.9, 0.25),
+            poison_food: Color::rgb(0.84, 0.37, 0.0),
+            slow_food: Color::rgb(0.0, 0.45, 0.7),
+            freeze_food: Color::rgb(0.8, 0.6, 0.7),
+            magnet_food: Color::rgb(0.9, 0.6, 0.0),
+            big_food: Color::rgb(0.0, 0.62, 0.45),
+            ..self
+        }
+    }
+}
+
+impl Theme {
+    fn palette(&self) -> Palette {
+        match self {
+            Self::Classic => Palette {
+                background: Color::rgb(0.04, 0.04, 0.04),
+                snake_heads: [Color::rgb(0.7, 0.7, 0.7), Color::rgb(0.45, 0.6, 0.85)],
+                snake_segments: [Color::rgb(0.3, 0.3, 0.3), Color::rgb(0.2, 0.3, 0.45)],
+                food: Color::rgb(1.0, 1.0, 1.0),
+                bonus_food: Color::rgb(1.0, 0.84, 0.0),
+                poison_food: Color::rgb(0.85, 0.1, 0.1),
+                slow_food: Color::rgb(0.2, 0.45, 1.0),
+                freeze_food: Color::rgb(0.4, 0.95, 0.95),
+                magnet_food: Color::rgb(0.9, 0.3, 0.7),
+                big_food: Color::rgb(0.3, 0.85, 0.4),
+            },
+            Self::Neon => Palette {
+                background: Color::rgb(0.02, 0.0, 0.06),
+                snake_heads: [Color::rgb(0.2, 1.0, 0.6), Color::rgb(1.0, 0.3, 0.9)],
+                snake_segments: [Color::rgb(0.0, 0.6, 0.35), Color::rgb(0.6, 0.1, 0.55)],
+                food: Color::rgb(0.2, 0.9, 1.0),
+                bonus_food: Color::rgb(1.0, 1.0, 0.2),
+                poison_food: Color::rgb(1.0, 0.1, 0.3),
+                slow_food: Color::rgb(0.1, 0.5, 1.0),
+                freeze_food: Color::rgb(0.5, 1.0, 1.0),
+                magnet_food: Color::rgb(1.0, 0.5, 0.0),
+                big_food: Color::rgb(0.6, 1.0, 0.2),
+            },
+            Self::Mono => Palette {
+                background: Color::rgb(0.0, 0.0, 0.0),
+                snake_heads: [Color::rgb(1.0, 1.0, 1.0), Color::rgb(0.8, 0.8, 0.8)],
+                snake_segments: [Color::rgb(0.5, 0.5, 0.5), Color::rgb(0.35, 0.35, 0.35)],
+                food: Color::rgb(0.9, 0.9, 0.9),
+                bonus_food: Color::rgb(0.65, 0.65, 0.65),
+                poison_food: Color::rgb(0.25, 0.25, 0.25),
+                slow_food: Color::rgb(0.8, 0.8, 0.8),
+                freeze_food: Color::rgb(0.55, 0.55, 0.55),
+                magnet_food: Color::rgb(0.7, 0.7, 0.7),
+                big_food: Color::rgb(1.0, 1.0, 1.0),
+            },
+        }
+    }
+
+    fn next(&self) -> Self {
+        match self {
+            Self::Classic => Self::Neon,
+            Self::Neon => Self::Mono,
+            Self::Mono => Self::Classic,
+        }
+    }
+}
+
+fn cycle_theme(keyboard: Res<Input<KeyCode>>, mut theme: ResMut<Theme>) {
+    if keyboard.just_pressed(KeyCode::T) {
+        *theme = theme.next();
+    }
+}
+
+// Recolors everything already on the board. Anything spawned later picks up
+// the theme when it's spawned. The background is `score_background`'s to set.
+fn apply_theme(
+    theme: Res<Theme>,
+    snakes: Query<(&SnakeHead, &SnakeSegments)>,
+    mut snake_sprites: Query<&mut Sprite, Without<Food>>,
+    mut food: Query<(&mut Sprite, &FoodKind), With<Food>>,
+) {
+    if !theme.is_changed() {
+        return;
+    }
+
+    let palette = theme.palette();
+
+    for (head, segments) in &snakes {
+        for (i, segment) in segments.iter().enumerate() {
+            if let Ok(mut sprite) = snake_sprites.get_mut(*segment) {
+                sprite.color = palette.snake_segment(head.player, i);
+            }
+        }
+    }
+
+    for (mut sprite, kind) in &mut food {
+        sprite.color = palette.food_color(*kind);
+    }
+}
+
+// Shifts the theme's background subtly towards another color as the score goes
+// up, and back again when it starts over.
+#[derive(Default, Deref, DerefMut)]
+struct ScoreBackground(bool);
+
+fn toggle_score_background(
+    keyboard: Res<Input<KeyCode>>,
+    mut score_background: ResMut<ScoreBackground>,
+) {
+    if keyboard.just_pressed(KeyCode::N) {
+        **score_background = !**score_background;
+    }
+}
+
+fn score_background(
+    theme: Res<Theme>,
+    score: Res<Score>,
+    score_background: Res<ScoreBackground>,
+    mut clear_color: ResMut<ClearColor>,
+) {
+    if !(theme.is_changed() || score.is_changed() || score_background.is_changed()) {
+        return;
+    }
+
+    let amount = if **score_background {
+        (**score as f32 / SCORE_BACKGROUND_FULL_SCORE).min(1.0) * SCORE_BACKGROUND_MIX
+    } else {
+        0.0
+    };
+    let (from, to) = (theme.palette().background, SCORE_BACKGROUND_COLOR);
+    let mix = |from: f32, to: f32| from + (to - from) * amount;
+    let color = Color::rgb(
+        mix(from.r(), to.r()),
+        mix(from.g(), to.g()),
+        mix(from.b(), to.b()),
+    );
+
+    // Only set when it's actually moved, so it isn't marked changed every time
+    // a point is scored past the full amount.
+    if clear_color.0 != color {
+        clear_color.0 = color;
+    }
+}
+
+#[derive(Default, Deref, DerefMut)]
+struct RainbowSkin(bool);
+
+fn toggle_rainbow_skin(keyboard: Res<Input<KeyCode>>, mut rainbow_skin: ResMut<RainbowSkin>) {
+    if keyboard.just_pressed(KeyCode::K) {
+        **rainbow_skin = !**rainbow_skin;
+    }
+}
+
+// Colors each snake along a hue gradient from head to tail. It goes over every
+// frame, as it's simpler than chasing which segments have moved up the body or
+// just been spawned at the end, and switching it off puts the theme's colors
+// back.
+fn rainbow_skin(
+    rainbow_skin: Res<RainbowSkin>,
+    theme: Res<Theme>,
+    snakes: Query<(&SnakeHead, &SnakeSegments)>,
+    mut sprites: Query<&mut Sprite, With<SnakeSegment>>,
+) {
+    if !**rainbow_skin && !rainbow_skin.is_changed() {
+        return;
+    }
+
+    let palette = theme.palette();
+
+    for (head, segments) in &snakes {
+        for (i, segment) in segments.iter().enumerate() {
+            if let Ok(mut sprite) = sprites.get_mut(*segment) {
+                sprite.color = if **rainbow_skin {
+                    // Stop short of going all the way round, so the tail
+                    // doesn't come back to the head's color, and start the
+                    // second snake on the opposite side of the wheel.
+                    let ratio = i as f32 / segments.len() as f32;
+                    let hue = (ratio * 300.0 + head.player as f32 * 180.0) % 360.0;
+                    Color::hsl(hue, 0.8, 0.55)
+                } else {
+                    palette.snake_segment(head.player, i)
+                };
+            }
+        }
+    }
+}
+
+// Gives every kind of food its own shape on top of its own color, so telling
+// them apart doesn't come down to color at all. Snakes keep the theme's
+// colors, since they're told apart by where they are anyway.
+#[derive(Default, Deref, DerefMut)]
+struct ColorblindMode(bool);
+
+#[derive(Clone, Copy)]
+enum FoodShape {
+    Circle,
+    Star,
+    Cross,
+    Ring,
+    Plus,
+    Triangle,
+    Diamond,
+}
+
+impl FoodShape {
+    const ALL: [Self; 7] = [
+        Self::Circle,
+        Self::Star,
+        Self::Cross,
+        Self::Ring,
+        Self::Plus,
+        Self::Triangle,
+        Self::Diamond,
+    ];
+
+    fn of(kind: FoodKind) -> Self {
+        match kind {
+            FoodKind::Normal => Self::Circle,
+            FoodKind::Bonus => Self::Star,
+            FoodKind::Poison => Self::Cross,
+            FoodKind::Slow => Self::Ring,
+            FoodKind::Big => Self::Diamond,
+            FoodKind::Freeze => Self::Plus,
+            FoodKind::Magnet => Self::Triangle,
+        }
+    }
+
+    // Whether a point is inside the shape, with the image running from -1 to 1
+    // across and y pointing up.
+    fn covers(&self, x: f32, y: f32) -> bool {
+        let distance = x.hypot(y);
+        match self {
+            Self::Circle => distance <= 0.9,
+            Self::Star => {
+                // Angles are measured from straight up, so a point's on top.
+                let angle = x.atan2(y);
+                distance <= 0.6 + 0.35 * (angle * 5.0).cos()
+            }
+            Self::Cross => {
+                x.abs() <= 0.85
+                    && y.abs() <= 0.85
+                    && ((x - y).abs() <= 0.35 || (x + y).abs() <= 0.35)
+            }
+            Self::Ring => (0.45..=0.9).contains(&distance),
+            Self::Plus => x.abs() <= 0.9 && y.abs() <= 0.9 && (x.abs() <= 0.3 || y.abs() <= 0.3),
+            // Pointing up, and widest along the bottom.
+            Self::Triangle => y >= -0.8 && x.abs() <= (0.9 - y) * 0.55,
+            Self::Diamond => x.abs() + y.abs() <= 0.95,
+        }
+    }
+
+    // A white image of the shape on a clear background, to be tinted with the
+    // food's color like any other sprite.
+    fn image(&self) -> Image {
+        let mut data = Vec::with_capacity((FOOD_SHAPE_PIXELS * FOOD_SHAPE_PIXELS * 4) as usize);
+        for row in 0..FOOD_SHAPE_PIXELS {
+            for column in 0..FOOD_SHAPE_PIXELS {
+                // The middle of each pixel, with the first row at the top.
+                let to_unit =
+                    |pixel: u32| (pixel as f32 + 0.5) / FOOD_SHAPE_PIXELS as f32 * 2.0 - 1.0;
+                let alpha = if self.covers(to_unit(column), -to_unit(row)) {
+                    255
+                } else {
+                    0
+                };
+                data.extend_from_slice(&[255, 255, 255, alpha]);
+            }
+        }
+
+        Image::new(
+            Extent3d {
+                width: FOOD_SHAPE_PIXELS,
+                height: FOOD_SHAPE_PIXELS,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+}
+
+// Indexed by `FoodShape`.
+struct FoodShapes([Handle<Image>; FoodShape::ALL.len()]);
+
+impl FoodShapes {
+    fn get(&self, shape: FoodShape) -> Handle<Image> {
+        self.0[shape as usize].clone()
+    }
+}
+
+// There's nowhere to put images without rendering, in which case food just
+// stays square.
+fn make_food_shapes(mut commands: Commands, images: Option<ResMut<Assets<Image>>>) {
+    if let Some(mut images) = images {
+        commands.insert_resource(FoodShapes(
+            FoodShape::ALL.map(|shape| images.add(shape.image())),
+        ));
+    }
+}
+
+fn toggle_colorblind_mode(
+    keyboard: Res<Input<KeyCode>>,
+    mut colorblind_mode: ResMut<ColorblindMode>,
+) {
+    if keyboard.just_pressed(KeyCode::O) {
+        **colorblind_mode = !**colorblind_mode;
+    }
+}
+
+// Reshapes and recolors all the food when the mode or the theme changes, and
+// any food that's just been spawned while it's on. Switching it off puts the
+// plain squares in the theme's colors back.
+fn apply_colorblind_mode(
+    colorblind_mode: Res<ColorblindMode>,
+    theme: Res<Theme>,
+    shapes: Option<Res<FoodShapes>>,
+    mut food: Query<
+        (
+            ChangeTrackers<Food>,
+            &mut Sprite,
+            &mut Handle<Image>,
+            &FoodKind,
+        ),
+        With<Food>,
+    >,
+) {
+    let everything = colorblind_mode.is_changed() || theme.is_changed();
+    if !**colorblind_mode && !everything {
+        return;
+    }
+
+    let palette = theme.palette();
+    let palette = if **colorblind_mode {
+        palette.colorblind()
+    } else {
+        palette
+    };
+
+    for (tracker, mut sprite, mut texture, kind) in &mut food {
+        if !everything && !tracker.is_added() {
+            continue;
+        }
+
+        sprite.color = palette.food_color(*kind);
+        match shapes.as_ref().filter(|_| **colorblind_mode) {
+            Some(shapes) => {
+                *texture = shapes.get(FoodShape::of(*kind));
+                // Like the snake's images, drawn one pixel across for `Size`
+                // to scale up.
+                sprite.custom_size = Some(Vec2::ONE);
+            }
+            None => {
+                *texture = Handle::default();
+                sprite.custom_size = None;
+            }
+        }
+    }
+}
+
+struct Sounds {
+    eat: Handle<AudioSource>,
+    death: Handle<AudioSource>,
+}
+
+#[derive(Deref, DerefMut)]
+struct Volume(f32);
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self(0.5)
+    }
+}
+
+#[derive(SystemParam)]
+struct SoundEffects<'w, 's> {
+    audio: Res<'w, Audio>,
+    sounds: Res<'w, Sounds>,
+    volume: Res<'w, Volume>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl SoundEffects<'_, '_> {
+    fn play(&self, sound: &Handle<AudioSource>) {
+        self.audio.play_with_settings(
+            sound.clone(),
+            PlaybackSettings::ONCE.with_volume(**self.volume),
+        );
+    }
+
+    fn eat(&self) {
+        self.play(&self.sounds.eat);
+    }
+
+    fn death(&self) {
+        self.play(&self.sounds.death);
+    }
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    // Setup the 2D camera system.
+    commands.spawn_bundle(Camera2dBundle::default());
+
+    commands.insert_resource(Sounds {
+        eat: asset_server.load(EAT_SOUND_PATH),
+        death: asset_server.load(DEATH_SOUND_PATH),
+    });
+
+    commands.insert_resource(SnakeSprites {
+        head: load_optional_image(&asset_server, SNAKE_HEAD_SPRITE_PATH),
+        body: load_optional_image(&asset_server, SNAKE_BODY_SPRITE_PATH),
+    });
+
+    let font = asset_server.load(FONT_PATH);
+
+    commands
+        .spawn_bundle(hud_text(
+            &font,
+            "Score: 0",
+            UiRect {
+                top: Val::Px(5.0),
+                left: Val::Px(5.0),
+                ..default()
+            },
+        ))
+        .insert(ScoreText);
+
+    commands
+        .spawn_bundle(hud_text(
+            &font,
+            "",
+            UiRect {
+                top: Val::Px(5.0),
+                right: Val::Px(5.0),
+                ..default()
+            },
+        ))
+        .insert(LivesText);
+
+    commands
+        .spawn_bundle(hud_text(
+            &font,
+            "PRACTICE",
+            UiRect {
+                top: Val::Px(30.0),
+                right: Val::Px(5.0),
+                ..default()
+            },
+        ))
+        .insert(PracticeIndicator)
+        .insert(Visibility { is_visible: false });
+
+    commands
+        .spawn_bundle(hud_text(
+            &font,
+            "FROZEN",
+            UiRect {
+                top: Val::Px(55.0),
+                right: Val::Px(5.0),
+                ..default()
+            },
+        ))
+        .insert(FrozenIndicator)
+        .insert(Visibility { is_visible: false });
+
+    commands
+        .spawn_bundle(hud_text(
+            &font,
+            "",
+            UiRect {
+                bottom: Val::Px(30.0),
+                left: Val::Px(5.0),
+                ..default()
+            },
+        ))
+        .insert(DailyIndicator)
+        .insert(Visibility { is_visible: false });
+
+    commands
+        .spawn_bundle(hud_text(
+            &font,
+            "",
+            UiRect {
+                bottom: Val::Px(55.0),
+                left: Val::Px(5.0),
+                ..default()
+            },
+        ))
+        .insert(PanicText)
+        .insert(Visibility { is_visible: false });
+
+    commands
+        .spawn_bundle(hud_text(
+            &font,
+            "00:00",
+            UiRect {
+                bottom: Val::Px(5.0),
+                left: Val::Px(5.0),
+                ..default()
+            },
+        ))
+        .insert(PlayTimeText);
+
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: font.clone(),
+                    color: Color::WHITE,
+                    font_size: 10.0,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(30.0),
+                    left: Val::Px(5.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(DebugOverlayText)
+        .insert(Visibility { is_visible: false });
+
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font,
+                    color: Color::WHITE,
+                    font_size: 10.0,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(5.0),
+                    right: Val::Px(5.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(FpsText)
+        .insert(Visibility { is_visible: false });
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: bevy::ui::Size::new(Val::Percent(100.0), Val::Px(3.0)),
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(0.0),
+                    left: Val::Px(0.0),
+                    ..default()
+                },
+                ..default()
+            },
+            color: LENGTH_BAR_TRACK_COLOR.into(),
+            ..default()
+        })
+        .insert(LengthBarUi)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: bevy::ui::Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                        ..default()
+                    },
+                    color: LENGTH_BAR_COLOR.into(),
+                    ..default()
+                })
+                .insert(LengthBarFill);
+        });
+}
+
+// Small text pinned to a corner of the window.
+fn hud_text(font: &Handle<Font>, value: &str, position: UiRect<Val>) -> TextBundle {
+    TextBundle::from_section(
+        value,
+        TextStyle {
+            font: font.clone(),
+            color: Color::WHITE,
+            font_size: 20.0,
+        },
+    )
+    .with_style(Style {
+        position_type: PositionType::Absolute,
+        position,
+        ..default()
+    })
+}
+
+// A transparent node covering the whole window that stacks its children
+// top-to-bottom in the middle of the screen.
+fn centered_column() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            size: bevy::ui::Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            // Column is bottom-to-top in UI space.
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        color: Color::NONE.into(),
+        ..default()
+    }
+}
+
+#[derive(Component)]
+struct MenuUi;
+
+// A line on the menu showing one of the options for the next run, filled in by
+// whatever system changes it.
+fn menu_setting(font: &Handle<Font>, margin_top: f32) -> TextBundle {
+    TextBundle::from_section(
+        "",
+        TextStyle {
+            font: font.clone(),
+            color: Color::WHITE,
+            font_size: 16.0,
+        },
+    )
+    .with_style(Style {
+        margin: UiRect {
+            top: Val::Px(margin_top),
+            ..default()
+        },
+        ..default()
+    })
+}
+
+fn setup_menu(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load(FONT_PATH);
+
+    commands
+        .spawn_bundle(centered_column())
+        .insert(MenuUi)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(
+                "SNAKE",
+                TextStyle {
+                    font: font.clone(),
+                    color: Color::WHITE,
+                    font_size: 60.0,
+                },
+            ));
+            parent
+                .spawn_bundle(menu_setting(&font, 30.0))
+                .insert(DifficultyText);
+            parent
+                .spawn_bundle(menu_setting(&font, 10.0))
+                .insert(PracticeText);
+            parent
+                .spawn_bundle(menu_setting(&font, 10.0))
+                .insert(ShapeText);
+            parent
+                .spawn_bundle(menu_setting(&font, 10.0))
+                .insert(EnemyText);
+            parent
+                .spawn_bundle(menu_setting(&font, 10.0))
+                .insert(SpeedRampText);
+            parent
+                .spawn_bundle(menu_setting(&font, 10.0))
+                .insert(DailyText);
+            for line in [
+                "Press Space to Start",
+                "Press V for Versus",
+                "Press H for Hard Mode",
+                "Press P to Watch Best Run",
+            ] {
+                parent.spawn_bundle(
+                    TextBundle::from_section(
+                        line,
+                        TextStyle {
+                            font: font.clone(),
+                            color: Color::WHITE,
+                            font_size: 16.0,
+                        },
+                    )
+                    .with_style(Style {
+                        margin: UiRect {
+                            top: Val::Px(30.0),
+                            ..default()
+                        },
+                        ..default()
+                    }),
+                );
+            }
+        });
+}
+
+fn menu(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    mut runs: Runs,
+    mut options: RunOptions,
+    mut replay: ResMut<Replay>,
+    mut replay_mode: ResMut<ReplayMode>,
+    mut daily: ResMut<DailyChallenge>,
+) {
+    if *runs.state != GameState::Menu {
+        return;
+    }
+
+    // Anything but a single player run started with Space isn't the daily
+    // challenge, whether or not it's switched on.
+    let space = keyboard.just_pressed(KeyCode::Space);
+    let daily_run = space && daily.date.is_some();
+
+    if space {
+        *runs.mode = GameMode::Single;
+        *replay_mode = ReplayMode::Record;
+        options.shrink.enabled = false;
+
+        // Everyone plays the daily challenge the same way, whatever they'd
+        // normally pick.
+        if daily_run {
+            options.play_by(&Replay::default());
+        }
+    } else if keyboard.just_pressed(KeyCode::V) {
+        *runs.mode = GameMode::Versus;
+        *replay_mode = ReplayMode::Record;
+        options.shrink.enabled = false;
+    } else if keyboard.just_pressed(KeyCode::H) {
+        *runs.mode = GameMode::Single;
+        *replay_mode = ReplayMode::Record;
+        options.shrink.enabled = true;
+    } else if keyboard.just_pressed(KeyCode::P) {
+        match load_replay() {
+            Some(best_run) => *replay = best_run,
+            None => return,
+        }
+        // The run has to be played back the way it was played, with the
+        // player's own options put back once it's over.
+        options.play_by(&replay);
+        *runs.mode = GameMode::Single;
+        *replay_mode = ReplayMode::Playback {
+            next_input: 0,
+            next_panic: 0,
+        };
+    } else {
+        return;
+    }
+
+    daily.active = daily_run;
+
+    // Clears away the demo along with anything left of the last run.
+    runs.start(&mut commands);
+}
+
+// Holds the snake still for a few seconds after it spawns so the player can
+// get ready. Turns are still accepted in the meantime.
+#[derive(Deref, DerefMut)]
+struct Countdown(Timer);
+
+impl Default for Countdown {
+    fn default() -> Self {
+        Self(Timer::from_seconds(COUNTDOWN_SECONDS, false))
+    }
+}
+
+// Plays itself behind the menu. Moves, eats and grows like any other snake,
+// but it's steered by `ai_steering` and its deaths only start the demo over.
+#[derive(Component)]
+struct AiSnake;
+
+fn demo_snake(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    spawner: SnakeSpawner,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    heads: Query<(), With<SnakeHead>>,
+    board: Query<Entity, With<GameEntity>>,
+) {
+    if *game_state != GameState::Menu {
+        return;
+    }
+
+    if game_over_reader.iter().next().is_some() {
+        for entity in &board {
+            commands.entity(entity).despawn_recursive();
+        }
+    } else if heads.is_empty() {
+        let head = spawner.spawn_snake(&mut commands, 0);
+        commands.entity(head).insert(AiSnake);
+    }
+}
+
+// The edges, walls and gaps in the arena the demo snake steers around.
+#[derive(SystemParam)]
+struct DemoBounds<'w, 's> {
+    wrap_mode: Res<'w, WrapMode>,
+    arena: Res<'w, EffectiveArena>,
+    mask: Res<'w, ArenaMask>,
+    walls: Query<'w, 's, &'static Position, With<Wall>>,
+}
+
+impl DemoBounds<'_, '_> {
+    // Where a step onto `next` really ends up, unless it's off an edge that
+    // doesn't wrap or into a wall.
+    fn enter(&self, next: Position) -> Option<Position> {
+        let next = self.wrap_mode.apply(next, &self.arena)?;
+        let blocked = self.walls.iter().any(|wall| *wall == next) || !self.mask.contains(&next);
+        (!blocked).then_some(next)
+    }
+}
+
+// Heads for the nearest food, taking whichever step gets it closest without
+// running into anything.
+fn ai_steering(
+    game_state: Res<GameState>,
+    bounds: DemoBounds,
+    mut heads: Query<(&mut SnakeHead, &SnakeSegments), With<AiSnake>>,
+    positions: Query<&Position>,
+    food: Query<&Position, With<Food>>,
+) {
+    if *game_state != GameState::Menu {
+        return;
+    }
+
+    for (mut head, segments) in &mut heads {
+        let body = segments
+            .iter()
+            .filter_map(|segment| positions.get(*segment).ok())
+            .copied()
+            .collect::<Vec<_>>();
+        let head_pos = body[0];
+
+        let distance_to_food = |position: Position| {
+            food.iter()
+                .map(|food| (food.x - position.x).abs() + (food.y - position.y).abs())
+                .min()
+                .unwrap_or_default()
+        };
+
+        let best = [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+        ]
+        .into_iter()
+        .filter(|direction| *direction != head.direction.opposite())
+        .filter_map(|direction| {
+            let mut next = head_pos;
+            match direction {
+                Direction::Left => next.x -= 1,
+                Direction::Right => next.x += 1,
+                Direction::Down => next.y -= 1,
+                Direction::Up => next.y += 1,
+            }
+
+            let next = bounds.enter(next).filter(|next| !body.contains(next))?;
+            Some((direction, distance_to_food(next)))
+        })
+        .min_by_key(|(_, distance)| *distance);
+
+        // Boxed in, so it may as well keep going.
+        if let Some((direction, _)) = best {
+            head.input_buffer.clear();
+            head.input_buffer.push_back(direction);
+        }
+    }
+}
+
+#[derive(Component)]
+struct CountdownUi;
+
+#[derive(Component)]
+struct CountdownText;
+
+fn countdown(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    game_state: Res<GameState>,
+    mut countdown: ResMut<Countdown>,
+    countdown_ui: Query<Entity, With<CountdownUi>>,
+    mut countdown_text: Query<&mut Text, With<CountdownText>>,
+) {
+    if *game_state != GameState::Running || countdown.finished() {
+        return;
+    }
+
+    if countdown.tick(time.delta()).finished() {
+        for entity in &countdown_ui {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+
+    let remaining = countdown.duration().as_secs_f32() - countdown.elapsed_secs();
+    let value = remaining.ceil().to_string();
+
+    if countdown_ui.is_empty() {
+        commands
+            .spawn_bundle(centered_column())
+            .insert(CountdownUi)
+            .with_children(|parent| {
+                parent
+                    .spawn_bundle(TextBundle::from_section(
+                        value,
+                        TextStyle {
+                            font: asset_server.load(FONT_PATH),
+                            color: Color::WHITE,
+                            font_size: 60.0,
+                        },
+                    ))
+                    .insert(CountdownText);
+            });
+    } else {
+        for mut text in &mut countdown_text {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+// What's on offer while paused, top to bottom.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PauseOption {
+    Resume,
+    Restart,
+    Quit,
+}
+
+impl PauseOption {
+    const ALL: [Self; 3] = [Self::Resume, Self::Restart, Self::Quit];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Resume => "Resume",
+            Self::Restart => "Restart",
+            Self::Quit => "Quit",
+        }
+    }
+}
+
+#[derive(Component)]
+struct PauseMenuUi;
+
+#[derive(Component)]
+struct PauseMenuOption(PauseOption);
+
+// Starts the run over, just like pressing R.
+struct RestartEvent;
+
+// Where picking each of the options leads.
+#[derive(SystemParam)]
+struct PauseActions<'w, 's> {
+    game_state: ResMut<'w, GameState>,
+    restart_writer: EventWriter<'w, 's, RestartEvent>,
+    app_exit_writer: EventWriter<'w, 's, AppExit>,
+}
+
+impl PauseActions<'_, '_> {
+    fn pick(&mut self, option: PauseOption) {
+        match option {
+            PauseOption::Resume => *self.game_state = GameState::Running,
+            PauseOption::Restart => self.restart_writer.send(RestartEvent),
+            PauseOption::Quit => self.app_exit_writer.send(AppExit),
+        }
+    }
+}
+
+// Dims the board while paused and lists what to do next, picked with the arrow
+// keys and Enter or Space.
+fn pause_menu(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    asset_server: Res<AssetServer>,
+    mut actions: PauseActions,
+    pause_menu_ui: Query<Entity, With<PauseMenuUi>>,
+    mut options: Query<(&PauseMenuOption, &mut Text)>,
+    mut selected: Local<usize>,
+) {
+    if *actions.game_state != GameState::Paused {
+        for entity in &pause_menu_ui {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+
+    if pause_menu_ui.is_empty() {
+        *selected = 0;
+
+        let font = asset_server.load(FONT_PATH);
+        commands
+            .spawn_bundle(NodeBundle {
+                color: PAUSE_OVERLAY_COLOR.into(),
+                ..centered_column()
+            })
+            .insert(PauseMenuUi)
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle::from_section(
+                    "Paused",
+                    TextStyle {
+                        font: font.clone(),
+                        color: Color::WHITE,
+                        font_size: 40.0,
+                    },
+                ));
+
+                for (index, option) in PauseOption::ALL.into_iter().enumerate() {
+                    let color = if index == 0 {
+                        Color::WHITE
+                    } else {
+                        PAUSE_OPTION_COLOR
+                    };
+
+                    parent
+                        .spawn_bundle(
+                            TextBundle::from_section(
+                                option.label(),
+                                TextStyle {
+                                    font: font.clone(),
+                                    color,
+                                    font_size: 20.0,
+                                },
+                            )
+                            .with_style(Style {
+                                margin: UiRect::all(Val::Px(10.0)),
+                                ..default()
+                            }),
+                        )
+                        .insert(PauseMenuOption(option));
+                }
+            });
+        return;
+    }
+
+    let count = PauseOption::ALL.len();
+    if keyboard.just_pressed(KeyCode::Up) {
+        *selected = (*selected + count - 1) % count;
+    }
+    if keyboard.just_pressed(KeyCode::Down) {
+        *selected = (*selected + 1) % count;
+    }
+
+    let option = PauseOption::ALL[*selected];
+    if keyboard.any_just_pressed([KeyCode::Return, KeyCode::Space]) {
+        actions.pick(option);
+    }
+
+    for (PauseMenuOption(other), mut text) in &mut options {
+        text.sections[0].style.color = if *other == option {
+            Color::WHITE
+        } else {
+            PAUSE_OPTION_COLOR
+        };
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum GameState {
+    #[default]
+    Menu,
+    Running,
+    Paused,
+    GameOver,
+    // Like `GameOver`, but with a full board.
+    Won,
+}
+
+impl GameState {
+    // Whether the board should be playing out, either for a run once its
+    // countdown's done or for the demo snake behind the menu.
+    fn simulating(&self, countdown: &Countdown) -> bool {
+        match self {
+            Self::Running => countdown.finished(),
+            Self::Menu => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum GameMode {
+    #[default]
+    Single,
+    // Two players on one keyboard, each trying to outlast the other.
+    Versus,
+}
+
+impl GameMode {
+    fn players(&self) -> usize {
+        match self {
+            Self::Single => 1,
+            Self::Versus => 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct ArenaSize {
+    width: u32,
+    height: u32,
+}
+
+impl Default for ArenaSize {
+    fn default() -> Self {
+        Self {
+            width: 10,
+            height: 10,
+        }
+    }
+}
+
+// Which tiles of the arena can be played on, for boards that aren't just a
+// rectangle. Anything outside the shape is as good as a wall.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum ArenaShape {
+    #[default]
+    Full,
+    Cross,
+    // The full arena with a hole cut out of the middle.
+    Donut,
+}
+
+impl ArenaShape {
+    fn name(&self) -> &str {
+        match self {
+            Self::Full => "Full",
+            Self::Cross => "Cross",
+            Self::Donut => "Donut",
+        }
+    }
+
+    fn next(&self) -> Self {
+        match self {
+            Self::Full => Self::Cross,
+            Self::Cross => Self::Donut,
+            Self::Donut => Self::Full,
+        }
+    }
+
+    fn includes(&self, position: Position, arena: &ArenaSize) -> bool {
+        // Twice as far from the middle of the arena as the tile is, which
+        // keeps it whole for arenas an even number of tiles across.
+        let dx = (2 * position.x + 1 - arena.width as i32).abs();
+        let dy = (2 * position.y + 1 - arena.height as i32).abs();
+
+        match self {
+            Self::Full => true,
+            // Each arm is just over half the arena across, leaving the
+            // corners out.
+            Self::Cross => dx <= arena.width as i32 / 2 || dy <= arena.height as i32 / 2,
+            Self::Donut => dx >= arena.width as i32 / 3 || dy >= arena.height as i32 / 3,
+        }
+    }
+}
+
+// Every tile in the arena that's in its shape.
+#[derive(Deref)]
+struct ArenaMask(HashSet<Position>);
+
+impl ArenaMask {
+    fn new(shape: ArenaShape, arena: &ArenaSize) -> Self {
+        Self(
+            (0..arena.width as i32)
+                .flat_map(|x| (0..arena.height as i32).map(move |y| Position { x, y }))
+                .filter(|position| shape.includes(*position, arena))
+                .collect(),
+        )
+    }
+}
+
+impl Default for ArenaMask {
+    fn default() -> Self {
+        Self::new(ArenaShape::default(), &ArenaSize::default())
+    }
+}
+
+#[derive(Component)]
+struct ShapeText;
+
+fn cycle_arena_shape(
+    keyboard: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    mut shape: ResMut<ArenaShape>,
+    mut text: Query<&mut Text, With<ShapeText>>,
+) {
+    if *game_state == GameState::Menu && keyboard.just_pressed(KeyCode::M) {
+        *shape = shape.next();
+    }
+
+    if shape.is_changed() {
+        for mut text in &mut text {
+            text.sections[0].value = format!("Arena: {} (M)", shape.name());
+        }
+    }
+}
+
+#[derive(Component)]
+struct MaskedTile;
+
+// Cut out again whenever the shape or the arena it's cut from changes, and
+// redrawn along with it. If either snake wouldn't start out wholly inside the
+// shape, it goes back to the full arena instead.
+fn update_arena_mask(
+    mut commands: Commands,
+    mut shape: ResMut<ArenaShape>,
+    mut mask: ResMut<ArenaMask>,
+    spawner: SnakeSpawner,
+    masked_tiles: Query<Entity, With<MaskedTile>>,
+) {
+    if !(shape.is_changed() || spawner.arena.is_changed() || spawner.start_length.is_changed()) {
+        return;
+    }
+
+    let cut = ArenaMask::new(*shape, &spawner.arena);
+    let misfit = (0..SNAKE_STARTS.len()).find(|player| {
+        spawner
+            .start_positions(*player)
+            .iter()
+            .any(|position| !cut.contains(position))
+    });
+    if let Some(player) = misfit {
+        warn!(
+            "Player {} wouldn't start inside the {} arena, using the full arena",
+            player + 1,
+            shape.name()
+        );
+        *shape = ArenaShape::Full;
+        return;
+    }
+    *mask = cut;
+
+    for entity in &masked_tiles {
+        commands.entity(entity).despawn();
+    }
+
+    for x in 0..spawner.arena.width as i32 {
+        for y in 0..spawner.arena.height as i32 {
+            let position = Position { x, y };
+            if mask.contains(&position) {
+                continue;
+            }
+
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: MASKED_COLOR,
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0.0, 0.0, BACKGROUND_Z),
+                    ..default()
+                })
+                .insert(MaskedTile)
+                .insert(position)
+                .insert(Size::square(1.0));
+        }
+    }
+}
+
+// A dev mode for trying out different sizes of arena, grown and shrunk a tile
+// at a time with + and -, whatever's going on.
+#[derive(Default, Deref, DerefMut)]
+struct Sandbox(bool);
+
+fn toggle_sandbox(keyboard: Res<Input<KeyCode>>, mut sandbox: ResMut<Sandbox>) {
+    if keyboard.just_pressed(KeyCode::F10) {
+        **sandbox = !**sandbox;
+        info!("Sandbox mode {}", if **sandbox { "on" } else { "off" });
+    }
+}
+
+fn resize_arena(
+    mut commands: Commands,
+    resize: SandboxResize,
+    game_mode: Res<GameMode>,
+    mut arena: ResMut<ArenaSize>,
+    mut effective: ResMut<EffectiveArena>,
+    segments: Query<&Position, With<SnakeSegment>>,
+    mut others: Query<
+        (Entity, &mut Position, Option<&mut BigFood>, Option<&Portal>),
+        (
+            Or<(With<Food>, With<Wall>, With<Portal>)>,
+            Without<SnakeSegment>,
+        ),
+    >,
+) {
+    let change = match resize.change() {
+        Some(change) => change,
+        None => return,
+    };
+
+    // Never so small that it cuts off a snake, or where one would start out
+    // after losing a life. Starting out lower down just means a shorter snake.
+    let starts = SNAKE_STARTS.iter().take(game_mode.players());
+    let min_width = segments
+        .iter()
+        .map(|position| position.x + 1)
+        .chain(starts.map(|(x, _)| x + 1))
+        .max()
+        .unwrap_or_default();
+    let min_height = segments
+        .iter()
+        .map(|position| position.y + 1)
+        .max()
+        .unwrap_or_default();
+
+    let resize = |size: u32, min: i32| {
+        (size as i32 + change).clamp(min.max(MIN_ARENA_SIZE as i32), MAX_ARENA_SIZE as i32) as u32
+    };
+    let (width, height) = (
+        resize(arena.width, min_width),
+        resize(arena.height, min_height),
+    );
+    if (width, height) == (arena.width, arena.height) {
+        return;
+    }
+
+    arena.width = width;
+    arena.height = height;
+    // If it was shrinking, it starts over from the new edges.
+    *effective = EffectiveArena::from(&*arena);
+
+    let fits = |position: Position, size: i32| {
+        position.x + size <= width as i32 && position.y + size <= height as i32
+    };
+    let size = |big: bool| if big { BIG_FOOD_SIZE } else { 1 };
+
+    let mut occupied = segments.iter().copied().collect::<HashSet<_>>();
+    for (_, position, big, _) in &others {
+        if fits(*position, size(big.is_some())) {
+            occupied.extend(food_tiles(*position, big));
+        }
+    }
+
+    // Anything else that's been left hanging over the edge gets pulled back
+    // onto it, unless there's already something there, in which case it goes.
+    // Food gets topped back up, and a portal takes its pair along with it.
+    let mut gone = HashSet::new();
+    let mut gone_portals = HashSet::new();
+    for (entity, mut position, big, portal) in &mut others {
+        let size = size(big.is_some());
+        if fits(*position, size) {
+            continue;
+        }
+
+        let pulled_back = Position {
+            x: position.x.min(width as i32 - size),
+            y: position.y.min(height as i32 - size),
+        };
+        let tiles = match big {
+            Some(_) => BigFood::new(pulled_back).tiles,
+            None => vec![pulled_back],
+        };
+        if tiles.iter().any(|tile| occupied.contains(tile)) {
+            commands.entity(entity).despawn();
+            gone.insert(entity);
+            gone_portals.extend(portal.map(|portal| portal.id));
+            continue;
+        }
+
+        occupied.extend(tiles);
+        *position = pulled_back;
+        if let Some(mut big) = big {
+            *big = BigFood::new(pulled_back);
+        }
+    }
+
+    for (entity, _, _, portal) in &others {
+        let pair_gone = portal.is_some_and(|portal| gone_portals.contains(&portal.id));
+        if pair_gone && !gone.contains(&entity) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+// + and - in sandbox mode.
+#[derive(SystemParam)]
+struct SandboxResize<'w, 's> {
+    keyboard: Res<'w, Input<KeyCode>>,
+    sandbox: Res<'w, Sandbox>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl SandboxResize<'_, '_> {
+    // How much bigger to make the arena each way, if it's being resized.
+    fn change(&self) -> Option<i32> {
+        if !**self.sandbox {
+            None
+        } else if self.keyboard.any_just_pressed([
+            KeyCode::Equals,
+            KeyCode::Plus,
+            KeyCode::NumpadAdd,
+        ]) {
+            Some(1)
+        } else if self
+            .keyboard
+            .any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract])
+        {
+            Some(-1)
+        } else {
+            None
+        }
+    }
+}
+
+// The part of the arena that's still in play. The whole arena unless it's been
+// shrinking, and always what gameplay goes by; `ArenaSize` is only for drawing.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct EffectiveArena {
+    left: i32,
+    bottom: i32,
+    width: u32,
+    height: u32,
+}
+
+impl From<&ArenaSize> for EffectiveArena {
+    fn from(arena: &ArenaSize) -> Self {
+        Self {
+            left: 0,
+            bottom: 0,
+            width: arena.width,
+            height: arena.height,
+        }
+    }
+}
+
+impl EffectiveArena {
+    fn right(&self) -> i32 {
+        self.left + self.width as i32 - 1
+    }
+
+    fn top(&self) -> i32 {
+        self.bottom + self.height as i32 - 1
+    }
+
+    fn area(&self) -> usize {
+        (self.width * self.height) as usize
+    }
+
+    fn contains(&self, position: Position) -> bool {
+        (self.left..=self.right()).contains(&position.x)
+            && (self.bottom..=self.top()).contains(&position.y)
+    }
+
+    // Where `from` would have to have been, off past an edge, for moving to
+    // `to` to be a single step, if that's how it got there by wrapping around.
+    fn unwrapped(&self, from: Position, to: Position) -> Option<Position> {
+        let (width, height) = (self.width as i32, self.height as i32);
+        [(width, 0), (-width, 0), (0, height), (0, -height)]
+            .into_iter()
+            .map(|(x, y)| Position {
+                x: from.x + x,
+                y: from.y + y,
+            })
+            .find(|from| (from.x - to.x).abs() + (from.y - to.y).abs() == 1)
+    }
+
+    // Wraps each axis on its own, however far out of the arena the position
+    // is.
+    fn wrap(&self, position: Position) -> Position {
+        Position {
+            x: self.left + (position.x - self.left).rem_euclid(self.width as i32),
+            y: self.bottom + (position.y - self.bottom).rem_euclid(self.height as i32),
+        }
+    }
+
+    // One tile in from every edge, until it's as small as it gets.
+    fn shrunk(&self) -> Self {
+        if self.width.min(self.height) <= MIN_SHRUNK_SIZE {
+            return *self;
+        }
+
+        Self {
+            left: self.left + 1,
+            bottom: self.bottom + 1,
+            width: self.width - 2,
+            height: self.height - 2,
+        }
+    }
+}
+
+// A hard mode where the arena closes in over the course of a run.
+struct ArenaShrink {
+    enabled: bool,
+    timer: Timer,
+}
+
+impl Default for ArenaShrink {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timer: Timer::from_seconds(SHRINK_INTERVAL, true),
+        }
+    }
+}
+
+// When the arena's next due to shrink.
+#[derive(SystemParam)]
+struct ShrinkClock<'w, 's> {
+    tick: Res<'w, MovementTick>,
+    game_state: Res<'w, GameState>,
+    shrink: ResMut<'w, ArenaShrink>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl ShrinkClock<'_, '_> {
+    // Follows the snake's clock, like the food, so replays shrink the same.
+    fn due(&mut self) -> bool {
+        self.shrink.enabled
+            && *self.game_state == GameState::Running
+            && self.tick.is_changed()
+            && self.shrink.timer.tick(self.tick.interval).just_finished()
+    }
+}
+
+// Anything caught outside when the arena shrinks is lost: food just goes, to be
+// topped back up inside, but a snake doesn't survive it.
+fn shrink_arena(
+    mut commands: Commands,
+    mut clock: ShrinkClock,
+    mut effective: ResMut<EffectiveArena>,
+    mut game_over_writer: EventWriter<GameOverEvent>,
+    snakes: Query<(&SnakeHead, &SnakeSegments)>,
+    positions: Query<&Position>,
+    food: Query<(Entity, &Position, Option<&BigFood>), Or<(With<Food>, With<Portal>)>>,
+) {
+    if !clock.due() {
+        return;
+    }
+
+    let shrunk = effective.shrunk();
+    if shrunk == *effective {
+        return;
+    }
+    *effective = shrunk;
+
+    let mut losers = snakes
+        .iter()
+        .filter(|(_, segments)| {
+            segments.iter().any(|segment| {
+                positions
+                    .get(*segment)
+                    .is_ok_and(|position| !effective.contains(*position))
+            })
+        })
+        .map(|(head, _)| head.player)
+        .collect::<Vec<_>>();
+    if !losers.is_empty() {
+        losers.sort_unstable();
+        game_over_writer.send(GameOverEvent { losers });
+    }
+
+    for (entity, position, big) in &food {
+        if food_tiles(*position, big)
+            .iter()
+            .any(|tile| !effective.contains(*tile))
+        {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+// Darkens what's already gone, and for the last few seconds before the next
+// shrink, marks the ring that's about to go too.
+#[derive(Component)]
+struct ShrinkOverlay;
+
+fn shrink_overlay(
+    mut commands: Commands,
+    arena: Res<ArenaSize>,
+    effective: Res<EffectiveArena>,
+    shrink: Res<ArenaShrink>,
+    game_state: Res<GameState>,
+    overlays: Query<Entity, With<ShrinkOverlay>>,
+    mut shown: Local<Option<(EffectiveArena, bool)>>,
+) {
+    let remaining = shrink.timer.duration().as_secs_f32() - shrink.timer.elapsed_secs();
+    let warning =
+        shrink.enabled && *game_state == GameState::Running && remaining <= SHRINK_WARNING;
+
+    if *shown == Some((*effective, warning)) {
+        return;
+    }
+    *shown = Some((*effective, warning));
+
+    for entity in &overlays {
+        commands.entity(entity).despawn();
+    }
+
+    let full = EffectiveArena::from(&*arena);
+    let mut rings = vec![(full, *effective, SHRUNK_COLOR)];
+    if warning {
+        rings.push((*effective, effective.shrunk(), SHRINK_WARNING_COLOR));
+    }
+
+    for (outer, inner, color) in rings {
+        // The band between the two as four rectangles, the top and bottom
+        // spanning the full width and the sides filling in between them.
+        let bands = [
+            (outer.left, outer.right(), outer.bottom, inner.bottom - 1),
+            (outer.left, outer.right(), inner.top() + 1, outer.top()),
+            (outer.left, inner.left - 1, inner.bottom, inner.top()),
+            (inner.right() + 1, outer.right(), inner.bottom, inner.top()),
+        ];
+
+        for (left, right, bottom, top) in bands {
+            if left > right || bottom > top {
+                continue;
+            }
+
+            let (width, height) = ((right - left + 1) as f32, (top - bottom + 1) as f32);
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite { color, ..default() },
+                    transform: Transform::from_xyz(0.0, 0.0, MARKER_Z),
+                    ..default()
+                })
+                .insert(ShrinkOverlay)
+                .insert(Position { x: left, y: bottom })
+                .insert(PositionOffset(Vec2::new(
+                    (width - 1.0) / 2.0,
+                    (height - 1.0) / 2.0,
+                )))
+                .insert(Size { width, height });
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct Position {
+    x: i32,
+    y: i32,
+}
+
+// Shifts an entity off the center of its tile, in tiles. Used for things like
+// grid lines that sit between tiles rather than on them.
+#[derive(Component, Deref, DerefMut)]
+struct PositionOffset(Vec2);
+
+#[derive(Component, Clone, Copy)]
+struct Size {
+    width: f32,
+    height: f32,
+}
+
+impl Size {
+    fn square(size: f32) -> Self {
+        Self {
+            width: size,
+            height: size,
+        }
+    }
+}
+
+fn size_scaling(
+    windows: Res<Windows>,
+    arena: Res<ArenaSize>,
+    mut query: Query<(&Size, &mut Transform)>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    for (sprite_size, mut transform) in &mut query {
+        transform.scale = Vec3::new(
+            sprite_size.width / arena.width as f32 * window.width(),
+            sprite_size.height / arena.height as f32 * window.height(),
+            1.0,
+        );
+    }
+}
+
+fn position_scaling(
+    windows: Res<Windows>,
+    arena: Res<ArenaSize>,
+    smooth_movement: Res<SmoothMovement>,
+    progress: Res<TickProgress>,
+    wrap_ghosts: Res<WrapGhosts>,
+    effective: Res<EffectiveArena>,
+    mut query: Query<(
+        &Position,
+        Option<&PositionOffset>,
+        Option<&PreviousPosition>,
+        &mut Transform,
+    )>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let wrap_across = wrap_ghosts.then(|| &*effective);
+    for (pos, offset, previous, mut transform) in &mut query {
+        let mut tile = drawn_tile(*pos, previous, **smooth_movement, **progress, wrap_across);
+        tile += offset.map_or(Vec2::ZERO, |offset| **offset);
+        transform.translation.x = convert_position(tile.x, window.width(), arena.width as f32);
+        transform.translation.y = convert_position(tile.y, window.height(), arena.height as f32);
+    }
+}
+
+// Where on the board an entity gets drawn, in tiles, which is partway back
+// towards where it came from while it's gliding. Given the arena, something
+// that's just wrapped around glides in from past the edge.
+fn drawn_tile(
+    position: Position,
+    previous: Option<&PreviousPosition>,
+    smooth_movement: bool,
+    progress: f32,
+    wrap_across: Option<&EffectiveArena>,
+) -> Vec2 {
+    let tile = Vec2::new(position.x as f32, position.y as f32);
+
+    if let Some(previous) = previous.filter(|_| smooth_movement) {
+        let from = wrap_across
+            .and_then(|arena| arena.unwrapped(previous.previous, position))
+            .unwrap_or(previous.previous);
+        let from = Vec2::new(from.x as f32, from.y as f32);
+        // Otherwise, sliding across the whole board to wrap around would look
+        // far stranger than just popping out the other side.
+        if from.distance_squared(tile) <= 1.0 {
+            return from.lerp(tile, progress);
+        }
+    }
+
+    tile
+}
+
+// While the snake glides, has segments that have just wrapped around slide in
+// from past the edge, with a copy of each sliding out past the edge it left, so
+// the snake reads as going straight through rather than jumping. It's only for
+// show: the copies have nothing to do with where anything really is.
+#[derive(Default, Deref, DerefMut)]
+struct WrapGhosts(bool);
+
+#[derive(Component)]
+struct WrapGhost;
+
+fn toggle_wrap_ghosts(keyboard: Res<Input<KeyCode>>, mut wrap_ghosts: ResMut<WrapGhosts>) {
+    if keyboard.just_pressed(KeyCode::X) {
+        **wrap_ghosts = !**wrap_ghosts;
+    }
+}
+
+// Like the connectors, the copies are kept up every frame, reusing last
+// frame's.
+fn wrap_ghosts(
+    mut commands: Commands,
+    wrap_ghosts: Res<WrapGhosts>,
+    smooth_movement: Res<SmoothMovement>,
+    progress: Res<TickProgress>,
+    arena: Res<EffectiveArena>,
+    segments: Query<
+        (&PreviousPosition, &Sprite, &Handle<Image>, &Size),
+        (With<SnakeSegment>, Without<WrapGhost>),
+    >,
+    mut ghosts: Query<
+        (
+            Entity,
+            &mut Position,
+            &mut PositionOffset,
+            &mut Sprite,
+            &mut Handle<Image>,
+            &mut Size,
+        ),
+        With<WrapGhost>,
+    >,
+) {
+    let mut wanted = Vec::new();
+
+    if **wrap_ghosts && **smooth_movement {
+        for (previous, sprite, texture, size) in &segments {
+            let from = match arena.unwrapped(previous.previous, previous.current) {
+                Some(from) => from,
+                None => continue,
+            };
+
+            // The way it went, carried on out past the edge it left.
+            let step = Vec2::new(
+                (previous.current.x - from.x) as f32,
+                (previous.current.y - from.y) as f32,
+            );
+            wanted.push((
+                previous.previous,
+                step * **progress,
+                sprite.clone(),
+                texture.clone(),
+                *size,
+            ));
+        }
+    }
+
+    let mut wanted = wanted.into_iter();
+    for (entity, mut position, mut offset, mut sprite, mut texture, mut size) in &mut ghosts {
+        match wanted.next() {
+            Some((wanted_position, wanted_offset, wanted_sprite, wanted_texture, wanted_size)) => {
+                *position = wanted_position;
+                **offset = wanted_offset;
+                *sprite = wanted_sprite;
+                *texture = wanted_texture;
+                *size = wanted_size;
+            }
+            None => commands.entity(entity).despawn(),
+        }
+    }
+
+    for (position, offset, sprite, texture, size) in wanted {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite,
+                texture,
+                transform: Transform::from_xyz(0.0, 0.0, FOREGROUND_Z),
+                ..default()
+            })
+            .insert(GameEntity)
+            .insert(WrapGhost)
+            .insert(position)
+            .insert(PositionOffset(offset))
+            .insert(size);
+    }
+}
+
+// Draws the snake gliding from tile to tile over the course of each movement
+// tick instead of jumping a whole tile at a time. Positions themselves still
+// only ever change a tile at a time.
+#[derive(Default, Deref, DerefMut)]
+struct SmoothMovement(bool);
+
+fn toggle_smooth_movement(
+    keyboard: Res<Input<KeyCode>>,
+    mut smooth_movement: ResMut<SmoothMovement>,
+) {
+    if keyboard.just_pressed(KeyCode::F6) {
+        **smooth_movement = !**smooth_movement;
+    }
+}
+
+/// How far through the current movement tick the game is, brought up to date
+/// every frame before anything's drawn. While the snakes are on the move it
+/// runs from 0 up to, but never quite reaching, 1. Otherwise it's 1, with
+/// everything sitting on its tile.
+///
+/// Anything drawn in between ticks can follow this to keep in step with the
+/// snakes, however often frames come.
+#[derive(Deref, DerefMut)]
+pub struct TickProgress(f32);
+
+impl TickProgress {
+    pub fn fraction(&self) -> f32 {
+        self.0
+    }
+}
+
+impl Default for TickProgress {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+fn tick_progress(
+    game_state: Res<GameState>,
+    countdown: Res<Countdown>,
+    movement_timer: Res<MovementTimer>,
+    mut progress: ResMut<TickProgress>,
+) {
+    match *game_state {
+        _ if game_state.simulating(&countdown) => **progress = movement_timer.percent(),
+        // Hold still wherever the snake was when the game paused.
+        GameState::Paused => {}
+        // Otherwise nothing's moving, so everything sits on its tile.
+        _ => **progress = 1.0,
+    }
+}
+
+// Where an entity was before it last moved, kept alongside where it moved to
+// so a move can be told apart from the ones before it.
+#[derive(Component)]
+struct PreviousPosition {
+    previous: Position,
+    current: Position,
+}
+
+impl PreviousPosition {
+    fn new(position: Position) -> Self {
+        Self {
+            previous: position,
+            current: position,
+        }
+    }
+}
+
+fn track_previous_positions(
+    mut query: Query<(&Position, &mut PreviousPosition), Changed<Position>>,
+) {
+    for (position, mut previous) in &mut query {
+        previous.previous = previous.current;
+        previous.current = *position;
+    }
+}
+
+fn convert_position(pos: f32, bound_window: f32, bound_game: f32) -> f32 {
+    let tile_size = bound_window / bound_game;
+    pos / bound_game * bound_window - (bound_window / 2.0) + (tile_size / 2.0)
+}
+
+#[derive(Deref, DerefMut)]
+struct GridVisible(bool);
+
+impl Default for GridVisible {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+#[derive(Component)]
+struct GridLine;
+
+// Redrawn whenever the arena changes size.
+fn spawn_grid(
+    mut commands: Commands,
+    arena: Res<ArenaSize>,
+    grid_visible: Res<GridVisible>,
+    grid_lines: Query<Entity, With<GridLine>>,
+) {
+    if !arena.is_changed() {
+        return;
+    }
+
+    for entity in &grid_lines {
+        commands.entity(entity).despawn();
+    }
+
+    let (width, height) = (arena.width as f32, arena.height as f32);
+
+    let vertical = (0..=arena.width).map(|x| {
+        (
+            Position { x: x as i32, y: 0 },
+            Vec2::new(-0.5, (height - 1.0) / 2.0),
+            Size {
+                width: 0.04,
+                height,
+            },
+        )
+    });
+    let horizontal = (0..=arena.height).map(|y| {
+        (
+            Position { x: 0, y: y as i32 },
+            Vec2::new((width - 1.0) / 2.0, -0.5),
+            Size {
+                width,
+                height: 0.04,
+            },
+        )
+    });
+
+    for (position, offset, size) in vertical.chain(horizontal) {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: GRID_COLOR,
+                    ..default()
+                },
+                transform: Transform::from_xyz(0.0, 0.0, BACKGROUND_Z),
+                visibility: Visibility {
+                    is_visible: **grid_visible,
+                },
+                ..default()
+            })
+            .insert(GridLine)
+            .insert(position)
+            .insert(PositionOffset(offset))
+            .insert(size);
+    }
+}
+
+fn toggle_grid(
+    keyboard: Res<Input<KeyCode>>,
+    mut grid_visible: ResMut<GridVisible>,
+    mut grid_lines: Query<&mut Visibility, With<GridLine>>,
+) {
+    if keyboard.just_pressed(KeyCode::G) {
+        **grid_visible = !**grid_visible;
+    }
+
+    if grid_visible.is_changed() {
+        for mut visibility in &mut grid_lines {
+            visibility.is_visible = **grid_visible;
+        }
+    }
+}
+
+// Every sprite's size and position is worked out from the window's current
+// size each frame, so the board rescales to fit either way.
+fn toggle_fullscreen(keyboard: Res<Input<KeyCode>>, mut windows: ResMut<Windows>) {
+    if !keyboard.just_pressed(KeyCode::F11) {
+        return;
+    }
+
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_mode(match window.mode() {
+            WindowMode::Windowed => WindowMode::BorderlessFullscreen,
+            _ => WindowMode::Windowed,
+        });
+    }
+}
+
+// A thin bar along the bottom of the window showing how much of the arena the
+// longest snake would cover, for a sense of scale once it's gotten long.
+#[derive(Deref, DerefMut)]
+struct LengthBar(bool);
+
+impl Default for LengthBar {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+#[derive(Component)]
+struct LengthBarUi;
+
+#[derive(Component)]
+struct LengthBarFill;
+
+fn length_bar(
+    keyboard: Res<Input<KeyCode>>,
+    mut length_bar: ResMut<LengthBar>,
+    arena: Res<ArenaSize>,
+    snakes: Query<&SnakeSegments>,
+    mut bars: Query<&mut Visibility, With<LengthBarUi>>,
+    mut fills: Query<&mut Style, With<LengthBarFill>>,
+) {
+    if keyboard.just_pressed(KeyCode::F8) {
+        **length_bar = !**length_bar;
+    }
+
+    for mut visibility in &mut bars {
+        visibility.is_visible = **length_bar;
+    }
+
+    let longest = snakes
+        .iter()
+        .map(|segments| segments.len())
+        .max()
+        .unwrap_or(0);
+    let filled = (longest as f32 / (arena.width * arena.height) as f32).min(1.0);
+    for mut style in &mut fills {
+        style.size.width = Val::Percent(filled * 100.0);
+    }
+}
+
+#[derive(Default, Deref, DerefMut)]
+struct ShowFps(bool);
+
+#[derive(Component)]
+struct FpsText;
+
+// Runs whatever state the game's in, so it's there to read while paused too.
+fn fps_counter(
+    keyboard: Res<Input<KeyCode>>,
+    diagnostics: Res<Diagnostics>,
+    movement_timer: Res<MovementTimer>,
+    mut show_fps: ResMut<ShowFps>,
+    mut texts: Query<(&mut Text, &mut Visibility), With<FpsText>>,
+) {
+    if keyboard.just_pressed(KeyCode::F7) {
+        **show_fps = !**show_fps;
+    }
+
+    for (mut text, mut visibility) in &mut texts {
+        visibility.is_visible = **show_fps;
+        if !**show_fps {
+            continue;
+        }
+
+        let fps = diagnostics
+            .get(FrameTimeDiagnosticsPlugin::FPS)
+            .and_then(|fps| fps.average())
+            .unwrap_or_default();
+        text.sections[0].value = format!(
+            "{:.0} FPS, {:.0}ms tick",
+            fps,
+            movement_timer.duration().as_secs_f32() * 1000.0
+        );
+    }
+}
+
+// Shows what each snake is up to, for tracking down movement and collision
+// bugs.
+#[derive(Default, Deref, DerefMut)]
+struct DebugOverlay(bool);
+
+#[derive(Component)]
+struct DebugOverlayText;
+
+fn debug_overlay(
+    keyboard: Res<Input<KeyCode>>,
+    mut debug_overlay: ResMut<DebugOverlay>,
+    movement_timer: Res<MovementTimer>,
+    heads: Query<(&SnakeHead, &Position, &SnakeSegments)>,
+    mut texts: Query<(&mut Text, &mut Visibility), With<DebugOverlayText>>,
+) {
+    if keyboard.just_pressed(KeyCode::F3) {
+        **debug_overlay = !**debug_overlay;
+    }
+
+    for (mut text, mut visibility) in &mut texts {
+        visibility.is_visible = **debug_overlay;
+        if !**debug_overlay {
+            continue;
+        }
+
+        let tick = movement_timer.duration().as_secs_f32();
+        let mut lines = vec![format!(
+            "Tick: {:.0}ms ({:.1}/s)",
+            tick * 1000.0,
+            1.0 / tick
+        )];
+
+        let mut heads = heads.iter().collect::<Vec<_>>();
+        heads.sort_by_key(|(head, _, _)| head.player);
+        lines.extend(heads.into_iter().map(|(head, position, segments)| {
+            format!(
+                "P{}: ({}, {}) {:?} len {}",
+                head.player + 1,
+                position.x,
+                position.y,
+                head.direction,
+                segments.len()
+            )
+        }));
+
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+// One side of the arena, only shown when it's deadly.
+#[derive(Component)]
+struct Border(Direction);
+
+// Redrawn whenever the arena changes size, like the grid.
+fn spawn_border(
+    mut commands: Commands,
+    arena: Res<ArenaSize>,
+    wrap_mode: Res<WrapMode>,
+    borders: Query<Entity, With<Border>>,
+) {
+    if !arena.is_changed() {
+        return;
+    }
+
+    for entity in &borders {
+        commands.entity(entity).despawn();
+    }
+
+    let (width, height) = (arena.width as f32, arena.height as f32);
+    let (center_x, center_y) = ((width - 1.0) / 2.0, (height - 1.0) / 2.0);
+    let thickness = 0.1;
+
+    let sides = [
+        (
+            Direction::Left,
+            Vec2::new(-0.5, center_y),
+            Size {
+                width: thickness,
+                height,
+            },
+        ),
+        (
+            Direction::Right,
+            Vec2::new(width - 0.5, center_y),
+            Size {
+                width: thickness,
+                height,
+            },
+        ),
+        (
+            Direction::Down,
+            Vec2::new(center_x, -0.5),
+            Size {
+                width,
+                height: thickness,
+            },
+        ),
+        (
+            Direction::Up,
+            Vec2::new(center_x, height - 0.5),
+            Size {
+                width,
+                height: thickness,
+            },
+        ),
+    ];
+
+    for (side, offset, size) in sides {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: BORDER_COLOR,
+                    ..default()
+                },
+                transform: Transform::from_xyz(0.0, 0.0, MARKER_Z),
+                visibility: Visibility {
+                    is_visible: wrap_mode.edge(side) != Edge::Wrap,
+                },
+                ..default()
+            })
+            .insert(Border(side))
+            .insert(Position { x: 0, y: 0 })
+            .insert(PositionOffset(offset))
+            .insert(size);
+    }
+}
+
+fn border_visibility(wrap_mode: Res<WrapMode>, mut borders: Query<(&Border, &mut Visibility)>) {
+    if wrap_mode.is_changed() {
+        for (Border(side), mut visibility) in &mut borders {
+            visibility.is_visible = wrap_mode.edge(*side) != Edge::Wrap;
+        }
+    }
+}
+
+#[derive(Component)]
+struct Wall;
+
+fn spawn_walls(mut commands: Commands) {
+    for &(x, y) in WALL_POSITIONS {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: WALL_COLOR,
+                    ..default()
+                },
+                transform: Transform::from_xyz(0.0, 0.0, FOREGROUND_Z),
+                ..default()
+            })
+            .insert(Wall)
+            .insert(Position { x, y })
+            .insert(Size::square(1.0));
+    }
+}
+
+#[derive(Component)]
+struct SnakeHead {
+    player: usize,
+    // The direction the head moved in on the last tick.
+    direction: Direction,
+    // Turns the player has made since, applied one per tick so quick
+    // sequences of turns aren't lost between ticks.
+    input_buffer: VecDeque<Direction>,
+    // Snakes sit still until they're first told which way to go, so the
+    // player gets to pick rather than having to react.
+    started: bool,
+}
+
+impl SnakeHead {
+    fn new(player: usize, direction: Direction) -> Self {
+        Self {
+            player,
+            direction,
+            input_buffer: VecDeque::with_capacity(INPUT_BUFFER_CAPACITY),
+            started: false,
+        }
+    }
+
+    // Whether the snake will move on the next tick.
+    fn moving(&self) -> bool {
+        self.started || !self.input_buffer.is_empty()
+    }
+
+    // The direction the head will move in on the next tick.
+    fn next_direction(&self) -> Direction {
+        self.input_buffer.front().copied().unwrap_or(self.direction)
+    }
+
+    // Which way the snake will be going once it's made every turn queued up.
+    fn heading(&self) -> Direction {
+        self.input_buffer.back().copied().unwrap_or(self.direction)
+    }
+
+    // Whether `queue_turn` would take this turn.
+    fn accepts(&self, direction: Direction) -> bool {
+        let previous = self.heading();
+
+        // Facing the same way isn't a turn, unless it's the way the snake is
+        // setting off in, and a snake head can't just turn around! A snake
+        // that's yet to move faces away from its body, so it can't set off
+        // back into itself either.
+        (direction != previous || !self.moving())
+            && direction != previous.opposite()
+            && self.input_buffer.len() < INPUT_BUFFER_CAPACITY
+    }
+
+    fn queue_turn(&mut self, direction: Direction) {
+        if !self.accepts(direction) {
+            return;
+        }
+
+        self.input_buffer.push_back(direction);
+    }
+}
+
+/// Which way a snake is heading.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Direction {
+    fn opposite(&self) -> Self {
+        match self {
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+            Self::Up => Self::Down,
+            Self::Down => Self::Up,
+        }
+    }
+
+    // A quarter turn to the right.
+    fn clockwise(&self) -> Self {
+        match self {
+            Self::Left => Self::Up,
+            Self::Up => Self::Right,
+            Self::Right => Self::Down,
+            Self::Down => Self::Left,
+        }
+    }
+
+    // The tile a step this way from `position` lands on.
+    fn step(&self, position: Position) -> Position {
+        let (x, y) = match self {
+            Self::Left => (-1, 0),
+            Self::Right => (1, 0),
+            Self::Down => (0, -1),
+            Self::Up => (0, 1),
+        };
+        Position {
+            x: position.x + x,
+            y: position.y + y,
+        }
+    }
+}
+
+// Images to draw the snake with, if there are any. Either one missing means
+// those parts are drawn as squares of the theme's colors instead.
+//
+// The images are tinted with the theme's colors either way, so they work best
+// drawn in shades of grey, with the head facing up.
+struct SnakeSprites {
+    head: Option<Handle<Image>>,
+    body: Option<Handle<Image>>,
+}
+
+fn load_optional_image(asset_server: &AssetServer, path: &str) -> Option<Handle<Image>> {
+    // Checking first keeps a missing image from turning into an invisible
+    // snake and an error in the log. It's asked of the asset server, so it
+    // looks wherever that loads from.
+    asset_server
+        .asset_io()
+        .is_file(std::path::Path::new(path))
+        .then(|| asset_server.load(path))
+}
+
+fn snake_sprite(texture: Option<&Handle<Image>>, color: Color) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color,
+            // Images are drawn at their size in pixels by default, but `Size`
+            // expects everything to start out one pixel across like the
+            // plain squares do.
+            custom_size: texture.map(|_| Vec2::ONE),
+            ..default()
+        },
+        texture: texture.cloned().unwrap_or_default(),
+        transform: Transform::from_xyz(0.0, 0.0, FOREGROUND_Z),
+        ..default()
+    }
+}
+
+// Everything that goes into spawning a snake, for the systems that do.
+#[derive(SystemParam)]
+struct SnakeSpawner<'w, 's> {
+    theme: Res<'w, Theme>,
+    sprites: Res<'w, SnakeSprites>,
+    start_length: Res<'w, StartLength>,
+    gap: Res<'w, SegmentGap>,
+    arena: Res<'w, ArenaSize>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl SnakeSpawner<'_, '_> {
+    fn spawn_snakes(&self, commands: &mut Commands, game_mode: GameMode) {
+        for player in 0..game_mode.players() {
+            self.spawn_snake(commands, player);
+        }
+    }
+
+    fn spawn_snake(&self, commands: &mut Commands, player: usize) -> Entity {
+        let positions = self.start_positions(player);
+        self.spawn_snake_at(commands, player, Direction::Up, &positions)
+    }
+
+    // Where a player's snake starts out, head first.
+    fn start_positions(&self, player: usize) -> Vec<Position> {
+        // The body trails straight down from the head, so however long it is
+        // has to fit in a column, and the head moves up to make room for it.
+        // There's always a head, even if there's no arena to fit it in.
+        let length = (**self.start_length).max(1).min(self.arena.height.max(1)) as i32;
+        let (x, start_y) = SNAKE_STARTS[player];
+        let y = start_y.max(length - 1).min(self.arena.height as i32 - 1);
+
+        (0..length).map(|i| Position { x, y: y - i }).collect()
+    }
+
+    // Builds a snake out of `positions`, head first.
+    fn spawn_snake_at(
+        &self,
+        commands: &mut Commands,
+        player: usize,
+        direction: Direction,
+        positions: &[Position],
+    ) -> Entity {
+        let mut head = commands
+            // Initialize the head of the snake using its corresponding component
+            // and a bundle containing a sprite.
+            .spawn_bundle(snake_sprite(
+                self.sprites.head.as_ref(),
+                self.theme.palette().snake_heads[player],
+            ));
+        head.insert(SnakeHead::new(player, direction))
+            .insert(GameEntity)
+            .insert(SnakeSegment)
+            .insert(positions[0])
+            .insert(PreviousPosition::new(positions[0]))
+            .insert(Size::square(self.gap.head_size()))
+            .insert(LastTailPosition::default());
+
+        // A head image can show which way it's facing on its own.
+        if self.sprites.head.is_none() {
+            head.with_children(|parent| {
+                // A notch marking the front of the head, in the head's local
+                // space, where the sprite spans -0.5..0.5 and faces up.
+                parent.spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: SNAKE_HEAD_MARK_COLOR,
+                        ..default()
+                    },
+                    transform: Transform {
+                        translation: Vec3::new(0.0, 0.3, 0.1),
+                        scale: Vec3::new(0.5, 0.15, 1.0),
+                        ..default()
+                    },
+                    ..default()
+                });
+            });
+        }
+
+        let head = head.id();
+        let mut segments = vec![head];
+        segments.extend(
+            positions[1..]
+                .iter()
+                .map(|position| self.spawn_segment(commands, player, *position)),
+        );
+
+        commands.entity(head).insert(SnakeSegments(segments));
+        head
+    }
+
+    fn spawn_segment(&self, commands: &mut Commands, player: usize, position: Position) -> Entity {
+        commands
+            .spawn_bundle(snake_sprite(
+                self.sprites.body.as_ref(),
+                self.theme.palette().snake_segments[player],
+            ))
+            .insert(GameEntity)
+            .insert(SnakeSegment)
+            .insert(position)
+            .insert(PreviousPosition::new(position))
+            .insert(Size::square(self.gap.segment_size()))
+            .id()
+    }
+}
+
+// How many tiles long snakes start out, head included.
+#[derive(Deref, DerefMut)]
+struct StartLength(u32);
+
+impl Default for StartLength {
+    fn default() -> Self {
+        Self(2)
+    }
+}
+
+// How much of its tile is left empty around each body segment, so they read as
+// separate pieces rather than one long block.
+#[derive(Deref, DerefMut)]
+struct SegmentGap(f32);
+
+impl Default for SegmentGap {
+    fn default() -> Self {
+        Self(0.35)
+    }
+}
+
+impl SegmentGap {
+    fn segment_size(&self) -> f32 {
+        (1.0 - **self).clamp(MIN_SEGMENT_SIZE, 1.0)
+    }
+
+    fn head_size(&self) -> f32 {
+        (self.segment_size() + HEAD_EXTRA_SIZE).min(1.0)
+    }
+}
+
+// Resizes the snakes already on the board. Anything spawned later is sized by
+// `SnakeSpawner`.
+fn apply_segment_gap(
+    gap: Res<SegmentGap>,
+    mut segments: Query<(&mut Size, Option<&SnakeHead>), With<SnakeSegment>>,
+) {
+    if !gap.is_changed() {
+        return;
+    }
+
+    for (mut size, head) in &mut segments {
+        *size = Size::square(if head.is_some() {
+            gap.head_size()
+        } else {
+            gap.segment_size()
+        });
+    }
+}
+
+// What happens when the head moves past an edge of the arena.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum Edge {
+    // Re-emerge on the opposite edge.
+    #[default]
+    Wrap,
+    // Die.
+    Wall,
+    // Turn off it to run along it instead.
+    Bounce,
+}
+
+// How the edges of the arena behave, the left and right edges going by `x`
+// and the top and bottom by `y`.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "SavedWrapMode", into = "SavedWrapMode")]
+struct WrapMode {
+    x: Edge,
+    y: Edge,
+}
+
+// How a wrap mode's saved. Wrapping or walls all round are saved the same as
+// from before each axis had an edge of its own, so older settings still load.
+#[derive(Serialize, Deserialize)]
+enum SavedWrapMode {
+    Wrap,
+    Walls,
+    Axes { x: Edge, y: Edge },
+}
+
+impl From<SavedWrapMode> for WrapMode {
+    fn from(saved: SavedWrapMode) -> Self {
+        let (x, y) = match saved {
+            SavedWrapMode::Wrap => (Edge::Wrap, Edge::Wrap),
+            SavedWrapMode::Walls => (Edge::Wall, Edge::Wall),
+            SavedWrapMode::Axes { x, y } => (x, y),
+        };
+        Self { x, y }
+    }
+}
+
+impl From<WrapMode> for SavedWrapMode {
+    fn from(wrap_mode: WrapMode) -> Self {
+        match (wrap_mode.x, wrap_mode.y) {
+            (Edge::Wrap, Edge::Wrap) => Self::Wrap,
+            (Edge::Wall, Edge::Wall) => Self::Walls,
+            (x, y) => Self::Axes { x, y },
+        }
+    }
+}
+
+impl WrapMode {
+    // The edge heading this way leads to.
+    fn edge(&self, direction: Direction) -> Edge {
+        match direction {
+            Direction::Left | Direction::Right => self.x,
+            Direction::Down | Direction::Up => self.y,
+        }
+    }
+
+    // Whether a head that's moved to `position` has gone over an edge it
+    // bounces off.
+    fn bounces(&self, position: Position, arena: &EffectiveArena) -> bool {
+        let wrapped = arena.wrap(position);
+        (self.x == Edge::Bounce && wrapped.x != position.x)
+            || (self.y == Edge::Bounce && wrapped.y != position.y)
+    }
+
+    // Where a head that's moved to `position` ends up, which is nowhere if it
+    // went into a wall. One that's already bounced and still went over an
+    // edge has nowhere left to go either.
+    fn apply(&self, position: Position, arena: &EffectiveArena) -> Option<Position> {
+        let wrapped = arena.wrap(position);
+        let axis = |edge: Edge, wrapped: i32, position: i32| match edge {
+            Edge::Wrap => Some(wrapped),
+            _ if wrapped == position => Some(position),
+            Edge::Wall | Edge::Bounce => None,
+        };
+
+        Some(Position {
+            x: axis(self.x, wrapped.x, position.x)?,
+            y: axis(self.y, wrapped.y, position.y)?,
+        })
+    }
+
+    // Wrapping all round, then walls all round, then wrapping side to side
+    // only, then top to bottom only, then bouncing all round.
+    fn next(&self) -> Self {
+        let (x, y) = match (self.x, self.y) {
+            (Edge::Wrap, Edge::Wrap) => (Edge::Wall, Edge::Wall),
+            (Edge::Wall, Edge::Wall) => (Edge::Wrap, Edge::Wall),
+            (Edge::Wrap, Edge::Wall) => (Edge::Wall, Edge::Wrap),
+            (Edge::Wall, Edge::Wrap) => (Edge::Bounce, Edge::Bounce),
+            _ => (Edge::Wrap, Edge::Wrap),
+        };
+        Self { x, y }
+    }
+}
+
+// Set when the walls go up mid-run, so a head that's already heading over the
+// edge gets one last wrap instead of dying the moment they appear.
+#[derive(Default, Deref, DerefMut)]
+struct WallGrace(bool);
+
+fn toggle_wrap_mode(
+    keyboard: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    daily: Res<DailyChallenge>,
+    replay_mode: Res<ReplayMode>,
+    mut wrap_mode: ResMut<WrapMode>,
+    mut wall_grace: ResMut<WallGrace>,
+) {
+    // The daily challenge keeps to the same edges for everyone, and a replay
+    // to the ones it was played with.
+    let locked = (daily.active && *game_state != GameState::Menu)
+        || matches!(*replay_mode, ReplayMode::Playback { .. });
+    if !keyboard.just_pressed(KeyCode::B) || locked {
+        return;
+    }
+
+    let previous = *wrap_mode;
+    *wrap_mode = previous.next();
+    **wall_grace = (previous.x == Edge::Wrap && wrap_mode.x == Edge::Wall)
+        || (previous.y == Edge::Wrap && wrap_mode.y == Edge::Wall);
+}
+
+#[derive(Deref, DerefMut)]
+struct MovementTimer(Timer);
+
+impl MovementTimer {
+    fn new(difficulty: Difficulty) -> Self {
+        Self(Timer::from_seconds(difficulty.base_interval(), true))
+    }
+}
+
+impl Default for MovementTimer {
+    fn default() -> Self {
+        Self::new(Difficulty::default())
+    }
+}
+
+// Speeds the snake up over the course of a run however long it's grown, so
+// there's no waiting it out. Whichever of this and growing is faster wins.
+struct SpeedRamp {
+    enabled: bool,
+    // Where it starts from, which is the difficulty's usual speed. Picked up
+    // when each run starts.
+    base: f32,
+    // How much faster every second makes each move.
+    per_second: f32,
+    floor: f32,
+}
+
+impl Default for SpeedRamp {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            base: Difficulty::default().base_interval(),
+            per_second: 0.001,
+            floor: MIN_MOVEMENT_INTERVAL,
+        }
+    }
+}
+
+impl SpeedRamp {
+    // Takes how long the run has been going, by `MovementTick` so that replays
+    // speed up at the same points.
+    fn interval(&self, elapsed: Duration) -> f32 {
+        (self.base - elapsed.as_secs_f32() * self.per_second).max(self.floor)
+    }
+}
+
+// Takes how many segments the snake has grown by since it started.
+fn movement_interval(grown: usize, difficulty: Difficulty) -> f32 {
+    (difficulty.base_interval() - grown as f32 * MOVEMENT_INTERVAL_STEP).max(MIN_MOVEMENT_INTERVAL)
+}
+
+// How fast the snake starts out, picked on the menu.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    fn base_interval(&self) -> f32 {
+        match self {
+            Self::Easy => 0.2,
+            Self::Normal => 0.15,
+            Self::Hard => 0.09,
+        }
+    }
+
+    // What everything eaten is multiplied by, so the faster settings are worth
+    // the risk. Normal food stays at its face value.
+    fn food_value(&self) -> usize {
+        match self {
+            Self::Easy | Self::Normal => 1,
+            Self::Hard => 3,
+        }
+    }
+
+    fn next(&self) -> Self {
+        match self {
+            Self::Easy => Self::Normal,
+            Self::Normal => Self::Hard,
+            Self::Hard => Self::Easy,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Easy => "Easy",
+            Self::Normal => "Normal",
+            Self::Hard => "Hard",
+        }
+    }
+}
+
+#[derive(Component)]
+struct DifficultyText;
+
+// A practice mode where snakes pass straight through themselves. Practice runs
+// don't count towards the high score.
+#[derive(Default, Deref, DerefMut)]
+struct Invincible(bool);
+
+#[derive(Component)]
+struct PracticeText;
+
+#[derive(Component)]
+struct PracticeIndicator;
+
+fn toggle_practice(
+    keyboard: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    mut invincible: ResMut<Invincible>,
+    mut texts: Query<&mut Text, With<PracticeText>>,
+    mut indicators: Query<&mut Visibility, With<PracticeIndicator>>,
+) {
+    if *game_state == GameState::Menu && keyboard.just_pressed(KeyCode::I) {
+        **invincible = !**invincible;
+    }
+
+    if invincible.is_changed() {
+        for mut text in &mut texts {
+            text.sections[0].value =
+                format!("Practice: {} (I)", if **invincible { "On" } else { "Off" });
+        }
+    }
+
+    for mut visibility in &mut indicators {
+        visibility.is_visible =
+            **invincible && matches!(*game_state, GameState::Running | GameState::Paused);
+    }
+}
+
+// A block that wanders the arena on the snake's clock, and is as deadly to run
+// into as a wall. It keeps out of the way of everything else, so it never
+// walks into a snake itself.
+#[derive(Default, Deref, DerefMut)]
+struct RoamingEnemy(bool);
+
+#[derive(Component)]
+struct Enemy {
+    direction: Direction,
+}
+
+#[derive(Component)]
+struct EnemyText;
+
+fn toggle_roaming_enemy(
+    keyboard: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    mut roaming_enemy: ResMut<RoamingEnemy>,
+    mut texts: Query<&mut Text, With<EnemyText>>,
+) {
+    if *game_state == GameState::Menu && keyboard.just_pressed(KeyCode::E) {
+        **roaming_enemy = !**roaming_enemy;
+    }
+
+    if roaming_enemy.is_changed() {
+        for mut text in &mut texts {
+            text.sections[0].value =
+                format!("Enemy: {} (E)", if **roaming_enemy { "On" } else { "Off" });
+        }
+    }
+}
+
+#[derive(Component)]
+struct SpeedRampText;
+
+fn toggle_speed_ramp(
+    keyboard: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    mut speed_ramp: ResMut<SpeedRamp>,
+    mut texts: Query<&mut Text, With<SpeedRampText>>,
+) {
+    if *game_state == GameState::Menu && keyboard.just_pressed(KeyCode::U) {
+        speed_ramp.enabled = !speed_ramp.enabled;
+    }
+
+    if speed_ramp.is_changed() {
+        for mut text in &mut texts {
+            text.sections[0].value = format!(
+                "Speed Up: {} (U)",
+                if speed_ramp.enabled { "On" } else { "Off" }
+            );
+        }
+    }
+}
+
+// Whether there's meant to be an enemy out, and when it's next due to move.
+#[derive(SystemParam)]
+struct EnemyClock<'w, 's> {
+    tick: Res<'w, MovementTick>,
+    game_state: Res<'w, GameState>,
+    roaming_enemy: Res<'w, RoamingEnemy>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl EnemyClock<'_, '_> {
+    fn enabled(&self) -> bool {
+        **self.roaming_enemy && *self.game_state != GameState::Menu
+    }
+
+    // Moves on the snake's clock so replays play out the same.
+    fn due(&self) -> bool {
+        *self.game_state == GameState::Running && self.tick.is_changed() && self.tick.count != 0
+    }
+}
+
+// Where in the arena an enemy can be, and the dice for picking.
+#[derive(SystemParam)]
+struct EnemyRoom<'w, 's> {
+    arena: Res<'w, EffectiveArena>,
+    mask: Res<'w, ArenaMask>,
+    rng: ResMut<'w, GameRng>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+// Turns up on the first tick of a run, or of a new life, since it's cleared
+// off the board along with everything else.
+fn roaming_enemy(
+    mut commands: Commands,
+    clock: EnemyClock,
+    room: EnemyRoom,
+    mut enemies: Query<(Entity, &mut Enemy, &mut Position)>,
+    blocked: Query<
+        (&Position, Option<&BigFood>),
+        (
+            Or<(With<SnakeSegment>, With<Wall>, With<Food>, With<Portal>)>,
+            Without<Enemy>,
+        ),
+    >,
+    heads: Query<&Position, (With<SnakeHead>, Without<Enemy>)>,
+) {
+    if !clock.enabled() {
+        for (entity, _, _) in &enemies {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+
+    if !clock.due() {
+        return;
+    }
+
+    let EnemyRoom {
+        arena,
+        mask,
+        mut rng,
+        ..
+    } = room;
+
+    let mut occupied = blocked
+        .iter()
+        .flat_map(|(position, big)| food_tiles(*position, big))
+        .collect::<HashSet<_>>();
+
+    if enemies.is_empty() {
+        // Give the snakes some room to get out of its way.
+        for head in &heads {
+            for x in -ENEMY_SPAWN_DISTANCE..=ENEMY_SPAWN_DISTANCE {
+                let reach = ENEMY_SPAWN_DISTANCE - x.abs();
+                for y in -reach..=reach {
+                    occupied.insert(Position {
+                        x: head.x + x,
+                        y: head.y + y,
+                    });
+                }
+            }
+        }
+
+        if let Some(position) = random_free_position(&arena, &mask, &occupied, &mut **rng) {
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: ENEMY_COLOR,
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0.0, 0.0, FOREGROUND_Z),
+                    ..default()
+                })
+                .insert(GameEntity)
+                .insert(Enemy {
+                    direction: Direction::Up,
+                })
+                .insert(position)
+                .insert(PreviousPosition::new(position))
+                .insert(Size::square(0.9));
+        }
+        return;
+    }
+
+    for (_, mut enemy, mut position) in &mut enemies {
+        let step = |direction: Direction| {
+            let mut next = *position;
+            match direction {
+                Direction::Left => next.x -= 1,
+                Direction::Right => next.x += 1,
+                Direction::Down => next.y -= 1,
+                Direction::Up => next.y += 1,
+            }
+            (arena.contains(next) && mask.contains(&next) && !occupied.contains(&next))
+                .then_some((direction, next))
+        };
+
+        // Carries on as it was going unless it can't or it feels like a turn,
+        // and only doubles back when there's nowhere else to go.
+        let ahead = step(enemy.direction);
+        let turns = [
+            Direction::Left,
+            Direction::Right,
+            Direction::Down,
+            Direction::Up,
+        ]
+        .into_iter()
+        .filter(|direction| {
+            *direction != enemy.direction && *direction != enemy.direction.opposite()
+        })
+        .filter_map(step)
+        .collect::<Vec<_>>();
+
+        let next = match ahead {
+            Some(ahead) if turns.is_empty() || rng.gen::<f32>() >= ENEMY_TURN_CHANCE => Some(ahead),
+            _ if !turns.is_empty() => Some(turns[rng.gen_range(0..turns.len())]),
+            _ => step(enemy.direction.opposite()),
+        };
+
+        // Boxed in on every side, so it waits it out.
+        if let Some((direction, next)) = next {
+            enemy.direction = direction;
+            *position = next;
+        }
+    }
+}
+
+fn cycle_difficulty(
+    keyboard: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    mut difficulty: ResMut<Difficulty>,
+    mut text: Query<&mut Text, With<DifficultyText>>,
+) {
+    if *game_state == GameState::Menu && keyboard.just_pressed(KeyCode::Tab) {
+        *difficulty = difficulty.next();
+    }
+
+    if difficulty.is_changed() {
+        for mut text in &mut text {
+            text.sections[0].value = format!(
+                "Difficulty: {}, food x{} (Tab)",
+                difficulty.name(),
+                difficulty.food_value()
+            );
+        }
+    }
+}
+
+// What goes into how fast the snakes move, bar slow-mo.
+#[derive(SystemParam)]
+struct Pace<'w, 's> {
+    difficulty: Res<'w, Difficulty>,
+    start_length: Res<'w, StartLength>,
+    speed_ramp: Res<'w, SpeedRamp>,
+    tick: Res<'w, MovementTick>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl Pace<'_, '_> {
+    // The movement interval with the longest snake `longest` segments long.
+    fn interval(&self, longest: usize) -> f32 {
+        let grown = longest.saturating_sub(**self.start_length as usize);
+        let interval = movement_interval(grown, *self.difficulty);
+        if self.speed_ramp.enabled {
+            interval.min(self.speed_ramp.interval(self.tick.elapsed))
+        } else {
+            interval
+        }
+    }
+}
+
+// Works out how long the next movement tick should be, after each one.
+fn movement_speed(
+    tick: Res<MovementTick>,
+    pace: Pace,
+    mut slow_mo: ResMut<SlowMo>,
+    mut movement_timer: ResMut<MovementTimer>,
+    snakes: Query<&SnakeSegments>,
+) {
+    // A run that's only just started hasn't moved yet, and the last run's
+    // snakes might still be around until the end of the frame.
+    if !tick.is_changed() || tick.count == 0 {
+        return;
+    }
+
+    slow_mo.timer.tick(tick.interval);
+
+    // Pick up any growth since the last tick for the next one.
+    let longest = snakes
+        .iter()
+        .map(|segments| segments.len())
+        .max()
+        .unwrap_or_default();
+    let interval = pace.interval(longest) * slow_mo.factor();
+    movement_timer.set_duration(Duration::from_secs_f32(interval));
+}
+
+// What eating the food that changes how the game runs for a while sets off.
+#[derive(SystemParam)]
+struct PowerUps<'w, 's> {
+    slow_mo: ResMut<'w, SlowMo>,
+    freeze: ResMut<'w, FreezeTimer>,
+    magnet: ResMut<'w, Magnet>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+// Stretches out the movement tick for a while after eating slow-mo food.
+struct SlowMo {
+    timer: Timer,
+}
+
+impl SlowMo {
+    // What to multiply the movement interval by right now.
+    fn factor(&self) -> f32 {
+        let remaining = self.timer.duration().as_secs_f32() - self.timer.elapsed_secs();
+        let strength = (remaining / SLOW_MO_FADE_SECONDS).clamp(0.0, 1.0);
+        1.0 + (SLOW_MO_FACTOR - 1.0) * strength
+    }
+}
+
+impl Default for SlowMo {
+    fn default() -> Self {
+        let mut timer = Timer::from_seconds(SLOW_MO_SECONDS, false);
+        // Nothing's slowed down until some slow-mo food gets eaten.
+        timer.tick(timer.duration());
+        Self { timer }
+    }
+}
+
+// Holds every snake still for a moment after eating freeze food. Turns still go
+// into the input buffer in the meantime, ready for when it wears off.
+#[derive(Deref, DerefMut)]
+struct FreezeTimer(Timer);
+
+impl Default for FreezeTimer {
+    fn default() -> Self {
+        let mut timer = Timer::from_seconds(FREEZE_SECONDS, false);
+        // Nothing's frozen until some freeze food gets eaten.
+        timer.tick(timer.duration());
+        Self(timer)
+    }
+}
+
+#[derive(Component)]
+struct FrozenIndicator;
+
+// Stops the movement timer where it is rather than holding off on the move
+// itself, so the snake picks up partway through the tick it froze in.
+fn freeze(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    countdown: Res<Countdown>,
+    mut freeze: ResMut<FreezeTimer>,
+    mut movement_timer: ResMut<MovementTimer>,
+    mut was_frozen: Local<bool>,
+    mut indicators: Query<&mut Visibility, With<FrozenIndicator>>,
+) {
+    if game_state.simulating(&countdown) && !freeze.finished() {
+        freeze.tick(time.delta());
+    }
+
+    // Only its start and end touch the timer, same as the death flash, so
+    // neither unpauses the other. Whichever frame it wears off on, the timer
+    // picks back up on the next.
+    let frozen = !freeze.finished();
+    if frozen && !*was_frozen {
+        movement_timer.pause();
+    } else if !frozen && *was_frozen {
+        movement_timer.unpause();
+    }
+    *was_frozen = frozen;
+
+    for mut visibility in &mut indicators {
+        visibility.is_visible = frozen;
+    }
+}
+
+// Pulls whichever food is nearest a player's head a tile closer to it every
+// tick, for a while after they eat magnet food. Food can be pulled right onto
+// the head, where it's eaten the same as ever, but never onto the rest of a
+// snake or anything else in the way. Big food is too big to budge.
+struct Magnet {
+    timer: Timer,
+    player: usize,
+}
+
+impl Default for Magnet {
+    fn default() -> Self {
+        let mut timer = Timer::from_seconds(MAGNET_SECONDS, false);
+        // Nothing's pulled in until some magnet food gets eaten.
+        timer.tick(timer.duration());
+        Self { timer, player: 0 }
+    }
+}
+
+// The magnet, and when it's next due to pull.
+#[derive(SystemParam)]
+struct MagnetClock<'w, 's> {
+    tick: Res<'w, MovementTick>,
+    game_state: Res<'w, GameState>,
+    magnet: ResMut<'w, Magnet>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl MagnetClock<'_, '_> {
+    // The player it's pulling towards, if it pulls this frame. Follows the
+    // snake's clock, like the food, so replays pull the same.
+    fn pull(&mut self) -> Option<usize> {
+        if *self.game_state != GameState::Running
+            || !self.tick.is_changed()
+            || self.magnet.timer.finished()
+        {
+            return None;
+        }
+        self.magnet.timer.tick(self.tick.interval);
+        Some(self.magnet.player)
+    }
+}
+
+fn magnet(
+    mut clock: MagnetClock,
+    arena: Res<EffectiveArena>,
+    mask: Res<ArenaMask>,
+    heads: Query<(&SnakeHead, &Position), Without<Food>>,
+    blocked: Query<
+        &Position,
+        (
+            Or<(With<SnakeSegment>, With<Wall>, With<Portal>, With<Enemy>)>,
+            Without<Food>,
+        ),
+    >,
+    mut food: Query<(&mut Position, Option<&BigFood>), With<Food>>,
+) {
+    let player = match clock.pull() {
+        Some(player) => player,
+        None => return,
+    };
+
+    let head = match heads.iter().find(|(head, _)| head.player == player) {
+        Some((_, head)) => *head,
+        None => return,
+    };
+
+    let occupied = blocked
+        .iter()
+        .copied()
+        .chain(
+            food.iter()
+                .flat_map(|(position, big)| food_tiles(*position, big)),
+        )
+        .filter(|position| *position != head)
+        .collect::<HashSet<_>>();
+
+    // Ties go to the lowest tile, so it's always the same food that's picked.
+    let distance = |position: &Position| (position.x - head.x).abs() + (position.y - head.y).abs();
+    let nearest = food
+        .iter_mut()
+        .filter(|(_, big)| big.is_none())
+        .min_by_key(|(position, _)| (distance(position), position.x, position.y));
+    let mut position = match nearest {
+        Some((position, _)) => position,
+        None => return,
+    };
+
+    // Closes in along whichever way it's further off first, and the other way
+    // if that's blocked.
+    let (dx, dy) = (head.x - position.x, head.y - position.y);
+    let across = Position {
+        x: position.x + dx.signum(),
+        y: position.y,
+    };
+    let along = Position {
+        x: position.x,
+        y: position.y + dy.signum(),
+    };
+    let steps = if dx.abs() >= dy.abs() {
+        [across, along]
+    } else {
+        [along, across]
+    };
+
+    if let Some(step) = steps.into_iter().find(|step| {
+        *step != *position
+            && arena.contains(*step)
+            && mask.contains(step)
+            && !occupied.contains(step)
+    }) {
+        *position = step;
+    }
+}
+
+fn head_rotation(mut heads: Query<(&SnakeHead, &mut Transform)>) {
+    for (head, mut transform) in &mut heads {
+        // Follow the queued direction so the head turns as soon as the player
+        // does, not on the next tick.
+        let angle = match head.next_direction() {
+            Direction::Up => 0.0,
+            Direction::Left => std::f32::consts::FRAC_PI_2,
+            Direction::Down => std::f32::consts::PI,
+            Direction::Right => -std::f32::consts::FRAC_PI_2,
+        };
+        transform.rotation = Quat::from_rotation_z(angle);
+    }
+}
+
+// The snakes' clock, which only runs while the game does.
+#[derive(SystemParam)]
+struct MovementClock<'w, 's> {
+    time: Res<'w, Time>,
+    timer: ResMut<'w, MovementTimer>,
+    tick: ResMut<'w, MovementTick>,
+    game_state: Res<'w, GameState>,
+    countdown: Res<'w, Countdown>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl MovementClock<'_, '_> {
+    // Whether it's time for the snakes to move this frame.
+    fn due(&mut self) -> bool {
+        self.game_state.simulating(&self.countdown)
+            && self.timer.tick(self.time.delta()).just_finished()
+    }
+}
+
+// Where a head can go, and what's waiting for it past the edges or in the way.
+#[derive(SystemParam)]
+struct MovementBounds<'w, 's> {
+    wrap_mode: Res<'w, WrapMode>,
+    invincible: Res<'w, Invincible>,
+    wall_grace: ResMut<'w, WallGrace>,
+    arena: Res<'w, EffectiveArena>,
+    mask: Res<'w, ArenaMask>,
+    hazards:
+        Query<'w, 's, &'static Position, (Or<(With<Wall>, With<Enemy>)>, Without<SnakeSegment>)>,
+}
+
+#[derive(SystemParam)]
+struct MovementEvents<'w, 's> {
+    game_over: EventWriter<'w, 's, GameOverEvent>,
+    wrap: EventWriter<'w, 's, WrapEvent>,
+}
+
+fn snake_movement(
+    mut clock: MovementClock,
+    mut heads: Query<(&mut SnakeHead, &SnakeSegments, &mut LastTailPosition)>,
+    mut positions: Query<&mut Position, (With<SnakeSegment>, Without<Portal>)>,
+    mut portals: Query<(&Portal, &Position, &mut PortalLife)>,
+    mut bounds: MovementBounds,
+    mut events: MovementEvents,
+    mut recorder: ReplayRecorder,
+) {
+    if !clock.due() {
+        return;
+    }
+
+    // Everything logged while the snakes move is filed under the tick, which
+    // costs next to nothing with debug logging off.
+    let _span = debug_span!("movement_tick", tick = clock.tick.count).entered();
+    clock.tick.interval = clock.timer.duration();
+
+    let wrap_mode = if **bounds.wall_grace {
+        WrapMode::default()
+    } else {
+        *bounds.wrap_mode
+    };
+    **bounds.wall_grace = false;
+    let arena = &*bounds.arena;
+
+    // Where every snake's body will be once they've all moved, so running into
+    // another snake is just as deadly as running into yourself. Each segment
+    // takes the place of the one ahead, so that's everywhere but the tails,
+    // which leaves chasing a tail legal. A snake that's just grown has its new
+    // segments stacked on its tail, so there the tail's tile stays taken, as
+    // does all of a snake that's yet to move.
+    let occupied = heads
+        .iter()
+        .flat_map(|(head, segments, _)| {
+            let moving = if head.moving() { 1 } else { 0 };
+            segments.iter().take(segments.len() - moving)
+        })
+        .map(|e| *positions.get(*e).unwrap())
+        .collect::<HashSet<_>>();
+
+    let mut losers = Vec::new();
+    let mut new_heads = Vec::new();
+
+    for (mut head, segments, mut last_tail_position) in &mut heads {
+        if let Some(turn) = head.input_buffer.pop_front() {
+            // Check against the direction actually moved in, so nothing that
+            // got buffered can reverse the head into its own neck.
+            if turn != head.direction.opposite() {
+                head.direction = turn;
+                head.started = true;
+
+                if head.player == 0 {
+                    recorder.record(clock.tick.count, turn);
+                }
+            }
+        }
+
+        if !head.started {
+            continue;
+        }
+
+        let segment_positions = segments
+            .iter()
+            .map(|e| *positions.get(*e).unwrap())
+            .collect::<Vec<_>>();
+
+        let mut head_pos = head.direction.step(segment_positions[0]);
+
+        // The head only ever meets an edge head on, so bouncing off it turns
+        // it to run along the edge instead of doubling it back over its body.
+        // It turns clockwise, unless that's straight off another edge in a
+        // corner.
+        if wrap_mode.bounces(head_pos, arena) {
+            let clockwise = head.direction.clockwise();
+            head.direction = [clockwise, clockwise.opposite()]
+                .into_iter()
+                .find(|turn| {
+                    wrap_mode
+                        .apply(turn.step(segment_positions[0]), arena)
+                        .is_some()
+                })
+                .unwrap_or(clockwise);
+            head_pos = head.direction.step(segment_positions[0]);
+        }
+
+        let off_edge = head_pos;
+        head_pos = match wrap_mode.apply(head_pos, arena) {
+            Some(head_pos) => head_pos,
+            None => {
+                losers.push(head.player);
+                continue;
+            }
+        };
+
+        if head_pos != off_edge {
+            debug!(
+                player = head.player,
+                from_x = segment_positions[0].x,
+                from_y = segment_positions[0].y,
+                to_x = head_pos.x,
+                to_y = head_pos.y,
+                "Wrapped"
+            );
+            events.wrap.send(WrapEvent {
+                from: segment_positions[0],
+                to: head_pos,
+            });
+        }
+
+        head_pos = through_portal(head_pos, &mut portals);
+
+        // In practice the head passes through its own body, though not anyone
+        // else's. It never runs into its own neck either way: a turn can't
+        // reverse into it, so the only way there is a wrap or a portal leading
+        // straight back onto it, which isn't the player's doing.
+        let through_self = **bounds.invincible && segment_positions.contains(&head_pos);
+        let into_neck = segment_positions.get(1) == Some(&head_pos);
+        if (occupied.contains(&head_pos) && !through_self && !into_neck)
+            || bounds.hazards.iter().any(|hazard| *hazard == head_pos)
+            || !bounds.mask.contains(&head_pos)
+        {
+            losers.push(head.player);
+        }
+
+        *positions.get_mut(segments[0]).unwrap() = head_pos;
+
+        segment_positions
+            .iter()
+            .zip(segments.iter().skip(1))
+            .for_each(|(previous_seg_pos, current_segment)| {
+                *positions.get_mut(*current_segment).unwrap() = *previous_seg_pos;
+            });
+
+        *last_tail_position = LastTailPosition(segment_positions.iter().last().copied());
+        new_heads.push((head.player, head_pos));
+    }
+
+    // Two heads moving into the same tile take each other out.
+    for &(player, head_pos) in &new_heads {
+        if new_heads
+            .iter()
+            .any(|&(other, other_pos)| other != player && other_pos == head_pos)
+        {
+            losers.push(player);
+        }
+    }
+
+    if !losers.is_empty() {
+        losers.sort_unstable();
+        losers.dedup();
+        events.game_over.send(GameOverEvent { losers });
+    }
+
+    let tick = &mut *clock.tick;
+    tick.count += 1;
+    tick.elapsed += tick.interval;
+}
+
+// Stepping onto a portal comes out of the other half of its pair.
+fn through_portal(
+    position: Position,
+    portals: &mut Query<(&Portal, &Position, &mut PortalLife)>,
+) -> Position {
+    let entered = portals
+        .iter()
+        .find(|(_, portal_pos, _)| **portal_pos == position)
+        .map(|(portal, _, _)| portal.id);
+
+    let id = match entered {
+        Some(id) => id,
+        None => return position,
+    };
+
+    let mut exit = position;
+    for (portal, portal_pos, mut life) in portals {
+        if portal.id != id {
+            continue;
+        }
+
+        life.uses_left = life.uses_left.saturating_sub(1);
+        if *portal_pos != position {
+            exit = *portal_pos;
+        }
+    }
+
+    exit
+}
+
+// Sent when a head goes off one edge of the arena and comes back on the other.
+struct WrapEvent {
+    from: Position,
+    to: Position,
+}
+
+// Marks the tile on the far edge where a player's head would come back in if
+// it kept going.
+#[derive(Component)]
+struct WrapMarker {
+    player: usize,
+}
+
+fn wrap_markers(
+    mut commands: Commands,
+    wrap_mode: Res<WrapMode>,
+    game_state: Res<GameState>,
+    arena: Res<EffectiveArena>,
+    heads: Query<(&SnakeHead, &Position)>,
+    mut markers: Query<(Entity, &WrapMarker, &mut Position, &mut Visibility), Without<SnakeHead>>,
+) {
+    let playing = matches!(*game_state, GameState::Running | GameState::Paused);
+    let shown = |head: &SnakeHead| playing && wrap_mode.edge(head.next_direction()) == Edge::Wrap;
+
+    for (entity, marker, mut position, mut visibility) in &mut markers {
+        let head = heads.iter().find(|(head, _)| head.player == marker.player);
+
+        match head {
+            Some((head, head_pos)) => {
+                *position = reentry_position(head.next_direction(), *head_pos, &arena);
+                visibility.is_visible = shown(head);
+            }
+            // The snake's gone, so its marker goes with it.
+            None => commands.entity(entity).despawn(),
+        }
+    }
+
+    for (head, head_pos) in &heads {
+        if markers
+            .iter()
+            .any(|(_, marker, _, _)| marker.player == head.player)
+        {
+            continue;
+        }
+
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: WRAP_MARKER_COLOR,
+                    ..default()
+                },
+                transform: Transform::from_xyz(0.0, 0.0, MARKER_Z),
+                visibility: Visibility {
+                    is_visible: shown(head),
+                },
+                ..default()
+            })
+            .insert(WrapMarker {
+                player: head.player,
+            })
+            .insert(reentry_position(head.next_direction(), *head_pos, &arena))
+            .insert(Size::square(0.3));
+    }
+}
+
+fn reentry_position(direction: Direction, head_pos: Position, arena: &EffectiveArena) -> Position {
+    match direction {
+        Direction::Left => Position {
+            x: arena.right(),
+            ..head_pos
+        },
+        Direction::Right => Position {
+            x: arena.left,
+            ..head_pos
+        },
+        Direction::Down => Position {
+            y: arena.top(),
+            ..head_pos
+        },
+        Direction::Up => Position {
+            y: arena.bottom,
+            ..head_pos
+        },
+    }
+}
+
+// A brief highlight over both ends of a wrap that fades out on its own.
+#[derive(Component, Deref, DerefMut)]
+struct WrapFlash(Timer);
+
+fn wrap_flash(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut reader: EventReader<WrapEvent>,
+    mut flashes: Query<(Entity, &mut WrapFlash, &mut Sprite)>,
+) {
+    for event in reader.iter() {
+        for position in [event.from, event.to] {
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: WRAP_FLASH_COLOR,
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(0.0, 0.0, MARKER_Z),
+                    ..default()
+                })
+                .insert(GameEntity)
+                .insert(WrapFlash(Timer::from_seconds(WRAP_FLASH_SECONDS, false)))
+                .insert(position)
+                .insert(Size::square(1.0));
+        }
+    }
+
+    for (entity, mut flash, mut sprite) in &mut flashes {
+        if flash.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        } else {
+            sprite
+                .color
+                .set_a(WRAP_FLASH_COLOR.a() * flash.percent_left());
+        }
+    }
+}
+
+// In versus, player one uses the first key of each binding and player two the
+// second.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+struct KeyBindings {
+    up: Vec<KeyCode>,
+    down: Vec<KeyCode>,
+    left: Vec<KeyCode>,
+    right: Vec<KeyCode>,
+    pause: Vec<KeyCode>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            up: vec![KeyCode::Up, KeyCode::W],
+            down: vec![KeyCode::Down, KeyCode::S],
+            left: vec![KeyCode::Left, KeyCode::A],
+            right: vec![KeyCode::Right, KeyCode::D],
+            pause: vec![KeyCode::Escape],
+        }
+    }
+}
+
+// Has whichever turn the player made last before a tick be the one the snake
+// takes on it, instead of every turn waiting for a tick of its own. Quick
+// double turns go, in exchange for the snake always heading where the player
+// last asked it to.
+#[derive(Default, Deref, DerefMut)]
+struct PreTick(bool);
+
+fn toggle_pre_tick(keyboard: Res<Input<KeyCode>>, mut pre_tick: ResMut<PreTick>) {
+    if keyboard.just_pressed(KeyCode::F12) {
+        **pre_tick = !**pre_tick;
+    }
+}
+
+// Runs once all of a frame's input is in, and leaves just the turn to commit
+// to for `snake_movement` to pick up on the next tick. That's the last one
+// that wouldn't reverse the snake, as by then it's only the way the snake's
+// going now that counts. Replays queue their turns up for the tick they were
+// made on, so they're left as they are.
+fn commit_direction(
+    pre_tick: Res<PreTick>,
+    replay_mode: Res<ReplayMode>,
+    mut heads: Query<&mut SnakeHead>,
+) {
+    if !**pre_tick || matches!(*replay_mode, ReplayMode::Playback { .. }) {
+        return;
+    }
+
+    for mut head in &mut heads {
+        if head.input_buffer.len() < 2 {
+            continue;
+        }
+
+        let reverse = head.direction.opposite();
+        let committed = head
+            .input_buffer
+            .iter()
+            .rev()
+            .find(|turn| **turn != reverse)
+            .copied();
+
+        head.input_buffer.clear();
+        if let Some(committed) = committed {
+            head.input_buffer.push_back(committed);
+        }
+    }
+}
+
+fn snake_movement_input(
+    input: TurnInput,
+    mut game_state: ResMut<GameState>,
+    game_mode: Res<GameMode>,
+    replay_mode: Res<ReplayMode>,
+    mut last_gamepad_direction: Local<[Option<Direction>; SNAKE_STARTS.len()]>,
+    mut heads: Query<&mut SnakeHead>,
+) {
+    if input.pause_pressed() {
+        match *game_state {
+            GameState::Running => *game_state = GameState::Paused,
+            GameState::Paused => *game_state = GameState::Running,
+            _ => {}
+        }
+    }
+
+    // Ignore turns while paused so whatever direction was chosen before
+    // pausing is the one the snake resumes in. Replays steer themselves.
+    if *game_state != GameState::Running || matches!(*replay_mode, ReplayMode::Playback { .. }) {
+        return;
+    }
+
+    for mut head in &mut heads {
+        // In versus each player gets their own key from each binding and their
+        // own gamepad, otherwise anything goes.
+        let player = match *game_mode {
+            GameMode::Single => None,
+            GameMode::Versus => Some(head.player),
+        };
+
+        if let Some(direction) = input.keyboard(player, &head) {
+            head.queue_turn(direction);
+        }
+
+        // A held button or stick only counts once, when it first points
+        // somewhere new, the same as a key press. That way whichever was used
+        // last wins, rather than a controller left held that way turning the
+        // snake straight back after every key press. Within a frame there's no
+        // telling which came first, so the controller goes second.
+        //
+        // A turn that doesn't fit in the buffer is left to try again next
+        // frame, as it would be lost otherwise.
+        let gamepad = input.gamepad(player);
+        let last = &mut last_gamepad_direction[head.player];
+        if gamepad != *last && head.input_buffer.len() < INPUT_BUFFER_CAPACITY {
+            *last = gamepad;
+            if let Some(direction) = gamepad {
+                head.queue_turn(direction);
+            }
+        }
+    }
+}
+
+// Everywhere a turn can come from: the keys the player's bound, and any
+// gamepads.
+#[derive(SystemParam)]
+struct TurnInput<'w, 's> {
+    keyboard: Res<'w, Input<KeyCode>>,
+    bindings: Res<'w, KeyBindings>,
+    diagonal_policy: Res<'w, DiagonalPolicy>,
+    gamepads: Res<'w, Gamepads>,
+    gamepad_buttons: Res<'w, Input<GamepadButton>>,
+    gamepad_axes: Res<'w, Axis<GamepadAxis>>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl TurnInput<'_, '_> {
+    fn pause_pressed(&self) -> bool {
+        self.keyboard
+            .any_just_pressed(self.bindings.pause.iter().copied())
+    }
+
+    fn keyboard(&self, player: Option<usize>, head: &SnakeHead) -> Option<Direction> {
+        keyboard_direction(
+            &self.keyboard,
+            &self.bindings,
+            *self.diagonal_policy,
+            player,
+            head,
+        )
+    }
+
+    fn gamepad(&self, player: Option<usize>) -> Option<Direction> {
+        gamepad_direction(
+            &self.gamepads,
+            &self.gamepad_buttons,
+            &self.gamepad_axes,
+            player,
+        )
+    }
+}
+
+// Snakes only ever move along the grid, so there's no going diagonally. What
+// holding keys for two directions at right angles does instead, like Up and
+// Right together, is up to this.
+#[derive(Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum DiagonalPolicy {
+    // Carries on the way the snake's going when both go down in the same
+    // frame, so a stray finger on a second key can't turn it by accident. A key
+    // that was already being held doesn't count.
+    KeepDirection,
+    // Turns onto whichever of the two is at right angles to the way the
+    // snake's going.
+    #[default]
+    OtherAxis,
+}
+
+fn keyboard_direction(
+    keyboard: &Input<KeyCode>,
+    bindings: &KeyBindings,
+    diagonal_policy: DiagonalPolicy,
+    player: Option<usize>,
+    head: &SnakeHead,
+) -> Option<Direction> {
+    fn player_keys(keys: &[KeyCode], player: Option<usize>) -> &[KeyCode] {
+        match player {
+            Some(player) => keys.get(player..=player).unwrap_or_default(),
+            None => keys,
+        }
+    }
+
+    // Only fresh presses count, so holding one key down can't drown out a tap
+    // of another, and each press queues exactly one turn.
+    let pressed =
+        |keys: &[KeyCode]| keyboard.any_just_pressed(player_keys(keys, player).iter().copied());
+    let held = |keys: &[KeyCode]| keyboard.any_pressed(player_keys(keys, player).iter().copied());
+
+    let keys = |direction: Direction| match direction {
+        Direction::Left => &bindings.left,
+        Direction::Right => &bindings.right,
+        Direction::Down => &bindings.down,
+        Direction::Up => &bindings.up,
+    };
+
+    let diagonal = (held(&bindings.left) || held(&bindings.right))
+        && (held(&bindings.up) || held(&bindings.down));
+    let mashed = (pressed(&bindings.left) || pressed(&bindings.right))
+        && (pressed(&bindings.up) || pressed(&bindings.down));
+    let heading = head.heading();
+    if mashed && diagonal_policy == DiagonalPolicy::KeepDirection {
+        return None;
+    }
+
+    // Only one turn a frame, but only out of the ones that would actually turn
+    // the snake, so tapping back the way it came can't swallow a tap to the
+    // side at the same time.
+    [
+        Direction::Left,
+        Direction::Right,
+        Direction::Down,
+        Direction::Up,
+    ]
+    .into_iter()
+    .filter(|direction| head.accepts(*direction))
+    .filter(|direction| {
+        // A snake that's yet to set off could otherwise go either way.
+        !diagonal || (*direction != heading && *direction != heading.opposite())
+    })
+    .find(|direction| pressed(keys(*direction)))
+}
+
+fn gamepad_direction(
+    gamepads: &Gamepads,
+    buttons: &Input<GamepadButton>,
+    axes: &Axis<GamepadAxis>,
+    player: Option<usize>,
+) -> Option<Direction> {
+    let gamepads = gamepads
+        .iter()
+        .cloned()
+        .filter(|gamepad| player.is_none_or(|player| gamepad.id == player));
+
+    for gamepad in gamepads {
+        let pressed = |button_type| buttons.pressed(GamepadButton::new(gamepad, button_type));
+
+        let direction = if pressed(GamepadButtonType::DPadLeft) {
+            Some(Direction::Left)
+        } else if pressed(GamepadButtonType::DPadRight) {
+            Some(Direction::Right)
+        } else if pressed(GamepadButtonType::DPadDown) {
+            Some(Direction::Down)
+        } else if pressed(GamepadButtonType::DPadUp) {
+            Some(Direction::Up)
+        } else {
+            let axis = |axis_type| {
+                axes.get(GamepadAxis::new(gamepad, axis_type))
+                    .unwrap_or_default()
+            };
+            stick_direction(
+                axis(GamepadAxisType::LeftStickX),
+                axis(GamepadAxisType::LeftStickY),
+            )
+        };
+
+        if direction.is_some() {
+            return direction;
+        }
+    }
+
+    None
+}
+
+fn stick_direction(x: f32, y: f32) -> Option<Direction> {
+    // Sticks rarely sit at exactly zero, so ignore small amounts of drift.
+    if x.abs().max(y.abs()) < GAMEPAD_STICK_DEADZONE {
+        None
+    } else if x.abs() > y.abs() {
+        Some(if x < 0.0 {
+            Direction::Left
+        } else {
+            Direction::Right
+        })
+    } else {
+        Some(if y < 0.0 {
+            Direction::Down
+        } else {
+            Direction::Up
+        })
+    }
+}
+
+// Turns the snake in whichever direction a touch or mouse drag mostly went,
+// wherever on the screen it started.
+fn swipe_input(
+    touches: Res<Touches>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    game_state: Res<GameState>,
+    replay_mode: Res<ReplayMode>,
+    mut drag_start: Local<Option<Vec2>>,
+    mut heads: Query<&mut SnakeHead>,
+) {
+    let cursor = windows
+        .get_primary()
+        .and_then(|window| window.cursor_position());
+
+    if mouse_buttons.just_pressed(MouseButton::Left) {
+        *drag_start = cursor;
+    }
+
+    let mut swipes = touches
+        .iter_just_released()
+        .map(|touch| touch.position() - touch.start_position())
+        .collect::<Vec<_>>();
+
+    if mouse_buttons.just_released(MouseButton::Left) {
+        if let (Some(start), Some(end)) = (drag_start.take(), cursor) {
+            swipes.push(end - start);
+        }
+    }
+
+    if *game_state != GameState::Running || matches!(*replay_mode, ReplayMode::Playback { .. }) {
+        return;
+    }
+
+    for direction in swipes.into_iter().filter_map(swipe_direction) {
+        // Touch and mouse both steer player one, even in versus.
+        for mut head in heads.iter_mut().filter(|head| head.player == 0) {
+            head.queue_turn(direction);
+        }
+    }
+}
+
+fn swipe_direction(swipe: Vec2) -> Option<Direction> {
+    if swipe.length() < SWIPE_MIN_DISTANCE {
+        return None;
+    }
+
+    // Once normalized the dominant axis is always past the deadzone.
+    let swipe = swipe.normalize();
+    stick_direction(swipe.x, swipe.y)
+}
+
+// Everything that's spawned over the course of a run, rather than set up once
+// on startup, so whatever resets the board can clear all of it in one go.
+#[derive(Component)]
+struct GameEntity;
+
+#[derive(Component)]
+struct SnakeSegment;
+
+// Every segment of a snake in order, starting with the head. Lives on the head.
+#[derive(Component, Default, Deref, DerefMut)]
+struct SnakeSegments(Vec<Entity>);
+
+#[derive(Component)]
+struct Food;
+
+// What a piece of food does when it's eaten. Lives alongside `Food`.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum FoodKind {
+    Normal,
+    // Worth extra points, and disappears if it isn't eaten in time.
+    Bonus,
+    // Takes a segment off the snake instead of adding one.
+    Poison,
+    // Slows the snake down for a while instead of growing it.
+    Slow,
+    // Covers a square of tiles, with a `BigFood` saying which.
+    Big,
+    // Freezes everything for a moment instead of growing the snake.
+    Freeze,
+    // Sets off the magnet instead of growing the snake.
+    Magnet,
+}
+
+// How long bonus food has left before it disappears.
+#[derive(Component, Deref, DerefMut)]
+struct BonusFood(Timer);
+
+// When food's next due, and how much plain food there should be.
+#[derive(SystemParam)]
+struct FoodSupply<'w, 's> {
+    game_state: Res<'w, GameState>,
+    tick: Res<'w, MovementTick>,
+    timer: ResMut<'w, FoodTimer>,
+    count: Res<'w, FoodCount>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl FoodSupply<'_, '_> {
+    // Food follows the snake's clock rather than the wall clock so replays
+    // play out the same.
+    fn due(&mut self) -> bool {
+        matches!(*self.game_state, GameState::Running | GameState::Menu)
+            && self.tick.is_changed()
+            && self.timer.tick(self.tick.interval).just_finished()
+    }
+}
+
+// The arena and whatever's taking up room in it besides food, with the dice
+// for picking somewhere that isn't.
+#[derive(SystemParam)]
+struct FreeTiles<'w, 's> {
+    arena: Res<'w, EffectiveArena>,
+    mask: Res<'w, ArenaMask>,
+    rng: ResMut<'w, GameRng>,
+    segments: Query<'w, 's, &'static Position, With<SnakeSegment>>,
+    walls: Query<'w, 's, &'static Position, With<Wall>>,
+    portals: Query<'w, 's, &'static Position, With<Portal>>,
+}
+
+fn food_spawner(
+    mut commands: Commands,
+    food: Query<(&Position, &FoodKind, Option<&BigFood>), With<Food>>,
+    tiles: FreeTiles,
+    mut supply: FoodSupply,
+    mut game_won_writer: EventWriter<GameWonEvent>,
+    theme: Res<Theme>,
+    mut next_portal_id: Local<u32>,
+) {
+    if !supply.due() {
+        return;
+    }
+
+    let FreeTiles {
+        arena,
+        mask,
+        mut rng,
+        segments,
+        walls,
+        portals,
+    } = tiles;
+    let palette = theme.palette();
+    let mut occupied = segments
+        .iter()
+        .chain(walls.iter())
+        .chain(portals.iter())
+        .copied()
+        .chain(food.iter().flat_map(|(pos, _, big)| food_tiles(*pos, big)))
+        .collect::<HashSet<_>>();
+    let has = |wanted| food.iter().any(|(_, kind, _)| *kind == wanted);
+
+    // Keep the board topped up with exactly as much normal food as it's
+    // meant to have.
+    let normal_food = food
+        .iter()
+        .filter(|(_, kind, _)| **kind == FoodKind::Normal)
+        .count();
+    for spawned in 0..supply.count.saturating_sub(normal_food) {
+        match random_free_position(&arena, &mask, &occupied, &mut **rng) {
+            Some(position) => {
+                spawn_food(&mut commands, position, FoodKind::Normal, &palette);
+                occupied.insert(position);
+            }
+            // If the snake covers every tile there's nowhere left to put food.
+            None if normal_food + spawned == 0 => {
+                game_won_writer.send(GameWonEvent);
+                return;
+            }
+            // Otherwise there's still food left to finish off.
+            None => break,
+        }
+    }
+
+    if !has(FoodKind::Bonus) && rng.gen::<f32>() < BONUS_FOOD_CHANCE {
+        if let Some(position) = random_free_position(&arena, &mask, &occupied, &mut **rng) {
+            spawn_food(&mut commands, position, FoodKind::Bonus, &palette)
+                .insert(BonusFood(Timer::from_seconds(BONUS_FOOD_LIFETIME, false)));
+            occupied.insert(position);
+        }
+    }
+
+    if !has(FoodKind::Poison) && rng.gen::<f32>() < POISON_FOOD_CHANCE {
+        if let Some(position) = random_free_position(&arena, &mask, &occupied, &mut **rng) {
+            spawn_food(&mut commands, position, FoodKind::Poison, &palette);
+            occupied.insert(position);
+        }
+    }
+
+    if !has(FoodKind::Slow) && rng.gen::<f32>() < SLOW_FOOD_CHANCE {
+        if let Some(position) = random_free_position(&arena, &mask, &occupied, &mut **rng) {
+            spawn_food(&mut commands, position, FoodKind::Slow, &palette);
+            occupied.insert(position);
+        }
+    }
+
+    if !has(FoodKind::Big) && rng.gen::<f32>() < BIG_FOOD_CHANCE {
+        if let Some(corner) = random_free_block(&arena, &mask, &occupied, &mut **rng) {
+            let big = BigFood::new(corner);
+            occupied.extend(big.tiles.iter().copied());
+            spawn_big_food(&mut commands, corner, &palette);
+        }
+    }
+
+    if portals.is_empty() && rng.gen::<f32>() < PORTAL_CHANCE {
+        spawn_portal_pair(
+            &mut commands,
+            *next_portal_id,
+            &arena,
+            &mask,
+            &mut occupied,
+            &mut **rng,
+        );
+        *next_portal_id += 1;
+    }
+
+    // Rolled for last, so everything above rolls the same as it did before
+    // there was any freeze or magnet food.
+    if !has(FoodKind::Freeze) && rng.gen::<f32>() < FREEZE_FOOD_CHANCE {
+        if let Some(position) = random_free_position(&arena, &mask, &occupied, &mut **rng) {
+            spawn_food(&mut commands, position, FoodKind::Freeze, &palette);
+            occupied.insert(position);
+        }
+    }
+
+    if !has(FoodKind::Magnet) && rng.gen::<f32>() < MAGNET_FOOD_CHANCE {
+        if let Some(position) = random_free_position(&arena, &mask, &occupied, &mut **rng) {
+            spawn_food(&mut commands, position, FoodKind::Magnet, &palette);
+            occupied.insert(position);
+        }
+    }
+}
+
+fn random_free_position(
+    arena: &EffectiveArena,
+    mask: &ArenaMask,
+    occupied: &HashSet<Position>,
+    rng: &mut impl Rng,
+) -> Option<Position> {
+    // Re-rolling below would never terminate if every tile is taken.
+    let free = mask
+        .iter()
+        .filter(|position| arena.contains(**position) && !occupied.contains(position))
+        .count();
+    if free == 0 {
+        return None;
+    }
+
+    // Rolls the same for the full arena as it always has, so older replays
+    // still play out the same.
+    loop {
+        let position = Position {
+            x: rng.gen_range(arena.left..=arena.right()),
+            y: rng.gen_range(arena.bottom..=arena.top()),
+        };
+
+        if mask.contains(&position) && !occupied.contains(&position) {
+            return Some(position);
+        }
+    }
+}
+
+// Picks somewhere for the bottom-left corner of big food, with every one of its
+// tiles inside the arena and free.
+fn random_free_block(
+    arena: &EffectiveArena,
+    mask: &ArenaMask,
+    occupied: &HashSet<Position>,
+    rng: &mut impl Rng,
+) -> Option<Position> {
+    let corners = (arena.left..=arena.right() + 1 - BIG_FOOD_SIZE)
+        .flat_map(|x| {
+            (arena.bottom..=arena.top() + 1 - BIG_FOOD_SIZE).map(move |y| Position { x, y })
+        })
+        .filter(|corner| {
+            BigFood::new(*corner)
+                .tiles
+                .iter()
+                .all(|tile| mask.contains(tile) && !occupied.contains(tile))
+        })
+        .collect::<Vec<_>>();
+
+    (!corners.is_empty()).then(|| corners[rng.gen_range(0..corners.len())])
+}
+
+fn spawn_food<'w, 's, 'a>(
+    commands: &'a mut Commands<'w, 's>,
+    position: Position,
+    kind: FoodKind,
+    palette: &Palette,
+) -> EntityCommands<'w, 's, 'a> {
+    let mut food = commands.spawn_bundle(SpriteBundle {
+        sprite: Sprite {
+            color: palette.food_color(kind),
+            ..default()
+        },
+        transform: Transform::from_xyz(0.0, 0.0, FOREGROUND_Z),
+        ..default()
+    });
+    debug!(x = position.x, y = position.y, "Spawned food");
+    food.insert(GameEntity)
+        .insert(Food)
+        .insert(kind)
+        .insert(position)
+        .insert(Size::square(0.8));
+    food
+}
+
+// Food covering a square of tiles rather than just the one, worth more points
+// and more growth. It sits on the bottom-left of its tiles, and eating any of
+// them eats the lot.
+#[derive(Component)]
+struct BigFood {
+    tiles: Vec<Position>,
+}
+
+impl BigFood {
+    fn new(corner: Position) -> Self {
+        Self {
+            tiles: (0..BIG_FOOD_SIZE)
+                .flat_map(|x| {
+                    (0..BIG_FOOD_SIZE).map(move |y| Position {
+                        x: corner.x + x,
+                        y: corner.y + y,
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+fn spawn_big_food(commands: &mut Commands, corner: Position, palette: &Palette) {
+    // Drawn as one sprite over the middle of its tiles.
+    let offset = (BIG_FOOD_SIZE - 1) as f32 / 2.0;
+    spawn_food(commands, corner, FoodKind::Big, palette)
+        .insert(BigFood::new(corner))
+        .insert(Size::square(BIG_FOOD_SIZE as f32 - 0.2))
+        .insert(PositionOffset(Vec2::splat(offset)));
+}
+
+// Every tile a piece of food covers.
+fn food_tiles(position: Position, big: Option<&BigFood>) -> Vec<Position> {
+    big.map_or_else(|| vec![position], |big| big.tiles.clone())
+}
+
+// One of a pair of linked tiles, each leading to the other, sharing an `id`.
+#[derive(Component)]
+struct Portal {
+    id: u32,
+}
+
+// Kept in step on both halves of a pair, so they always go together.
+#[derive(Component)]
+struct PortalLife {
+    timer: Timer,
+    uses_left: u32,
+}
+
+fn spawn_portal_pair(
+    commands: &mut Commands,
+    id: u32,
+    arena: &EffectiveArena,
+    mask: &ArenaMask,
+    occupied: &mut HashSet<Position>,
+    rng: &mut impl Rng,
+) {
+    let first = random_free_position(arena, mask, occupied, rng);
+    if let Some(first) = first {
+        occupied.insert(first);
+    }
+    let second = random_free_position(arena, mask, occupied, rng);
+
+    // Half a pair wouldn't go anywhere.
+    let (first, second) = match (first, second) {
+        (Some(first), Some(second)) => (first, second),
+        _ => return,
+    };
+    occupied.insert(second);
+
+    for position in [first, second] {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: PORTAL_COLOR,
+                    ..default()
+                },
+                transform: Transform::from_xyz(0.0, 0.0, MARKER_Z),
+                ..default()
+            })
+            .insert(GameEntity)
+            .insert(Portal { id })
+            .insert(PortalLife {
+                timer: Timer::from_seconds(PORTAL_LIFETIME, false),
+                uses_left: PORTAL_USES,
+            })
+            .insert(position)
+            .insert(Size::square(0.9));
+    }
+}
+
+fn portal_expiry(
+    mut commands: Commands,
+    tick: Res<MovementTick>,
+    game_state: Res<GameState>,
+    mut portals: Query<(Entity, &mut PortalLife)>,
+) {
+    if !matches!(*game_state, GameState::Running | GameState::Menu) || !tick.is_changed() {
+        return;
+    }
+
+    for (entity, mut life) in &mut portals {
+        if life.timer.tick(tick.interval).finished() || life.uses_left == 0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn bonus_food_expiry(
+    mut commands: Commands,
+    tick: Res<MovementTick>,
+    game_state: Res<GameState>,
+    mut bonus_food: Query<(Entity, &mut BonusFood)>,
+) {
+    if !matches!(*game_state, GameState::Running | GameState::Menu) || !tick.is_changed() {
+        return;
+    }
+
+    for (entity, mut timer) in &mut bonus_food {
+        if timer.tick(tick.interval).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+struct GrowthEvent {
+    player: usize,
+    // How many segments to grow by.
+    segments: usize,
+}
+
+// How many segments each piece of food is worth.
+#[derive(Deref, DerefMut)]
+struct GrowthPerFood(usize);
+
+impl Default for GrowthPerFood {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+fn snake_eating(
+    mut commands: Commands,
+    food_positions: Query<(Entity, &Position, &Sprite, &FoodKind, Option<&BigFood>), With<Food>>,
+    mut heads: Query<(&SnakeHead, &Position, &mut SnakeSegments)>,
+    mut effects: FoodEffects,
+    game_state: Res<GameState>,
+    feedback: EatingFeedback,
+    mut respawner: FoodRespawner,
+) {
+    if !matches!(*game_state, GameState::Running | GameState::Menu) {
+        return;
+    }
+
+    let mut respawned = Vec::new();
+    // Two heads can each land on a different tile of the same big food.
+    let mut eaten = HashSet::new();
+    for (head, head_pos, mut segments) in &mut heads {
+        for (food_entity, food_pos, sprite, kind, big) in &food_positions {
+            if !food_tiles(*food_pos, big).contains(head_pos) || !eaten.insert(food_entity) {
+                continue;
+            }
+
+            commands.entity(food_entity).despawn();
+            // The demo snake plays quietly and for no points.
+            let demo = *game_state == GameState::Menu;
+            if !demo {
+                feedback.sound_effects.eat();
+            }
+            if **feedback.particles_enabled {
+                spawn_particle_burst(&mut commands, *food_pos, sprite.color);
+            }
+
+            if *kind == FoodKind::Poison {
+                // A snake that's down to just its head has nothing left to
+                // lose but the head itself.
+                if segments.len() <= 1 {
+                    effects.game_over_writer.send(GameOverEvent {
+                        losers: vec![head.player],
+                    });
+                } else if let Some(tail) = segments.pop() {
+                    commands.entity(tail).despawn();
+                }
+
+                if head.player == 0 && !demo {
+                    let lost = effects.score.min(POISON_FOOD_PENALTY);
+                    **effects.score -= lost;
+                    if lost > 0 {
+                        let font = feedback.asset_server.load(FONT_PATH);
+                        spawn_score_popup(&mut commands, font, *food_pos, format!("-{}", lost));
+                    }
+                }
+                continue;
+            }
+
+            // Eating more while it's still going starts it over, rather than
+            // slowing things down any further.
+            if *kind == FoodKind::Slow {
+                effects.power_ups.slow_mo.timer.reset();
+                continue;
+            }
+
+            // Same for freezing, and there's no freezing the demo snake.
+            if *kind == FoodKind::Freeze {
+                if !demo {
+                    effects.power_ups.freeze.reset();
+                }
+                continue;
+            }
+
+            // And the magnet goes over to whoever ate it last.
+            if *kind == FoodKind::Magnet {
+                if !demo {
+                    effects.power_ups.magnet.timer.reset();
+                    effects.power_ups.magnet.player = head.player;
+                }
+                continue;
+            }
+
+            effects.growth_writer.send(GrowthEvent {
+                player: head.player,
+                segments: if *kind == FoodKind::Big {
+                    BIG_FOOD_GROWTH
+                } else {
+                    **effects.growth_per_food
+                },
+            });
+
+            if *kind == FoodKind::Normal {
+                respawner.respawn(&mut commands, &mut respawned);
+            }
+
+            // Versus games are decided by who survives, so only player one's
+            // food counts.
+            if head.player == 0 && !demo {
+                let points = match kind {
+                    FoodKind::Bonus => BONUS_FOOD_POINTS,
+                    FoodKind::Big => BIG_FOOD_POINTS,
+                    _ => 1,
+                };
+                let points =
+                    points * effects.difficulty.food_value() * effects.combo.bump() as usize;
+                **effects.score += points;
+                let font = feedback.asset_server.load(FONT_PATH);
+                spawn_score_popup(&mut commands, font, *food_pos, format!("+{}", points));
+            }
+        }
+    }
+}
+
+// What comes of eating something: growing and scoring for food, and whatever
+// else the special kinds set off.
+#[derive(SystemParam)]
+struct FoodEffects<'w, 's> {
+    growth_writer: EventWriter<'w, 's, GrowthEvent>,
+    game_over_writer: EventWriter<'w, 's, GameOverEvent>,
+    score: ResMut<'w, Score>,
+    combo: ResMut<'w, Combo>,
+    growth_per_food: Res<'w, GrowthPerFood>,
+    difficulty: Res<'w, Difficulty>,
+    power_ups: PowerUps<'w, 's>,
+}
+
+// The sounds, particles and score popups that go along with eating.
+#[derive(SystemParam)]
+struct EatingFeedback<'w, 's> {
+    sound_effects: SoundEffects<'w, 's>,
+    particles_enabled: Res<'w, ParticlesEnabled>,
+    asset_server: Res<'w, AssetServer>,
+}
+
+#[derive(Deref, DerefMut)]
+struct ParticlesEnabled(bool);
+
+impl Default for ParticlesEnabled {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+fn toggle_particles(
+    keyboard: Res<Input<KeyCode>>,
+    mut particles_enabled: ResMut<ParticlesEnabled>,
+) {
+    if keyboard.just_pressed(KeyCode::F2) {
+        **particles_enabled = !**particles_enabled;
+    }
+}
+
+// Particles stay on their tile and drift away from its center by way of their
+// offset, so they scale with the window like everything else.
+#[derive(Component)]
+struct Particle {
+    // In tiles per second.
+    velocity: Vec2,
+    lifetime: Timer,
+}
+
+fn spawn_particle_burst(commands: &mut Commands, position: Position, color: Color) {
+    // Purely cosmetic, so this doesn't draw from `GameRng` and throw replays
+    // off.
+    let mut rng = thread_rng();
+
+    for i in 0..PARTICLE_COUNT {
+        let angle = (i as f32 + rng.gen::<f32>()) / PARTICLE_COUNT as f32 * std::f32::consts::TAU;
+
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite { color, ..default() },
+                transform: Transform::from_xyz(0.0, 0.0, FOREGROUND_Z),
+                ..default()
+            })
+            .insert(GameEntity)
+            .insert(Particle {
+                velocity: Vec2::new(angle.cos(), angle.sin()) * PARTICLE_SPEED,
+                lifetime: Timer::from_seconds(PARTICLE_LIFETIME, false),
+            })
+            .insert(position)
+            .insert(PositionOffset(Vec2::ZERO))
+            .insert(Size::square(0.15));
+    }
+}
+
+// Leaves a fading copy of the tail behind on every tile it leaves. Trails only
+// have a position to be drawn at, and nothing that checks what's on a tile ever
+// looks at them.
+#[derive(Default, Deref, DerefMut)]
+struct TrailEnabled(bool);
+
+#[derive(Component, Deref, DerefMut)]
+struct Trail(Timer);
+
+fn toggle_trail(keyboard: Res<Input<KeyCode>>, mut trail_enabled: ResMut<TrailEnabled>) {
+    if keyboard.just_pressed(KeyCode::L) {
+        **trail_enabled = !**trail_enabled;
+    }
+}
+
+fn spawn_trail(
+    mut commands: Commands,
+    trail_enabled: Res<TrailEnabled>,
+    tails: Query<(&SnakeSegments, &LastTailPosition), Changed<LastTailPosition>>,
+    segments: Query<(&Sprite, &Size)>,
+) {
+    if !**trail_enabled {
+        return;
+    }
+
+    for (snake, last_tail_position) in &tails {
+        let (position, (sprite, size)) = match (
+            last_tail_position.0,
+            snake.last().and_then(|tail| segments.get(*tail).ok()),
+        ) {
+            (Some(position), Some(tail)) => (position, tail),
+            _ => continue,
+        };
+
+        let mut color = sprite.color;
+        color.set_a(TRAIL_ALPHA);
+
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite { color, ..default() },
+                transform: Transform::from_xyz(0.0, 0.0, MARKER_Z),
+                ..default()
+            })
+            .insert(GameEntity)
+            .insert(Trail(Timer::from_seconds(TRAIL_SECONDS, false)))
+            .insert(position)
+            .insert(*size);
+    }
+}
+
+fn trail(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut trails: Query<(Entity, &mut Trail, &mut Sprite)>,
+) {
+    for (entity, mut trail, mut sprite) in &mut trails {
+        if trail.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        sprite.color.set_a(TRAIL_ALPHA * trail.percent_left());
+    }
+}
+
+// Fills in the gaps between neighboring segments so the snake reads as one
+// continuous body. Segments that are only next to each other by wrapping
+// around, or by going through a portal, are left apart.
+#[derive(Default, Deref, DerefMut)]
+struct ConnectedBody(bool);
+
+#[derive(Component)]
+struct Connector;
+
+fn toggle_connected_body(keyboard: Res<Input<KeyCode>>, mut connected_body: ResMut<ConnectedBody>) {
+    if keyboard.just_pressed(KeyCode::C) {
+        **connected_body = !**connected_body;
+    }
+}
+
+// Everything `drawn_tile` goes by besides the entity itself.
+#[derive(SystemParam)]
+struct DrawnTiles<'w, 's> {
+    smooth_movement: Res<'w, SmoothMovement>,
+    progress: Res<'w, TickProgress>,
+    wrap_ghosts: Res<'w, WrapGhosts>,
+    effective: Res<'w, EffectiveArena>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl DrawnTiles<'_, '_> {
+    fn get(&self, position: Position, previous: Option<&PreviousPosition>) -> Vec2 {
+        drawn_tile(
+            position,
+            previous,
+            **self.smooth_movement,
+            **self.progress,
+            self.wrap_ghosts.then(|| &*self.effective),
+        )
+    }
+}
+
+// Connectors are redrawn every frame rather than just when the snake moves, so
+// they keep up with smooth movement and with anything recoloring the body.
+fn connected_body(
+    mut commands: Commands,
+    connected_body: Res<ConnectedBody>,
+    gap: Res<SegmentGap>,
+    drawn: DrawnTiles,
+    snakes: Query<&SnakeSegments>,
+    segments: Query<(&Position, Option<&PreviousPosition>, &Sprite), Without<Connector>>,
+    mut connectors: Query<
+        (
+            Entity,
+            &mut Position,
+            &mut PositionOffset,
+            &mut Size,
+            &mut Sprite,
+        ),
+        With<Connector>,
+    >,
+) {
+    let mut wanted = Vec::new();
+
+    if **connected_body {
+        let thickness = gap.segment_size();
+
+        for snake in &snakes {
+            for pair in snake.windows(2) {
+                let (front, back) = match (segments.get(pair[0]), segments.get(pair[1])) {
+                    (Ok(front), Ok(back)) => (front, back),
+                    _ => continue,
+                };
+
+                let (front_position, back_position) = (*front.0, *back.0);
+                let apart = (front_position.x - back_position.x).abs()
+                    + (front_position.y - back_position.y).abs();
+                if apart != 1 {
+                    continue;
+                }
+
+                let from = drawn.get(front_position, front.1);
+                let to = drawn.get(back_position, back.1);
+                let span = (from - to).abs();
+                let size = Size {
+                    width: span.x.max(thickness),
+                    height: span.y.max(thickness),
+                };
+
+                // The head's color is its own, so connectors take after the
+                // body segment behind them.
+                wanted.push((
+                    front_position,
+                    (from + to) / 2.0 - Vec2::new(front_position.x as f32, front_position.y as f32),
+                    size,
+                    back.2.color,
+                ));
+            }
+        }
+    }
+
+    // Reuses the connectors from last frame, spawning or despawning the
+    // difference.
+    let mut wanted = wanted.into_iter();
+    for (entity, mut position, mut offset, mut size, mut sprite) in &mut connectors {
+        match wanted.next() {
+            Some((wanted_position, wanted_offset, wanted_size, color)) => {
+                *position = wanted_position;
+                **offset = wanted_offset;
+                *size = wanted_size;
+                sprite.color = color;
+            }
+            None => commands.entity(entity).despawn(),
+        }
+    }
+
+    for (position, offset, size, color) in wanted {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite { color, ..default() },
+                // Just under the segments, which cover either end of it.
+                transform: Transform::from_xyz(0.0, 0.0, FOREGROUND_Z - 0.1),
+                ..default()
+            })
+            .insert(GameEntity)
+            .insert(Connector)
+            .insert(position)
+            .insert(PositionOffset(offset))
+            .insert(size);
+    }
+}
+
+#[derive(Component, Deref, DerefMut)]
+struct ScorePopup(Timer);
+
+fn spawn_score_popup(
+    commands: &mut Commands,
+    font: Handle<Font>,
+    position: Position,
+    text: String,
+) {
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text::from_section(
+                text,
+                TextStyle {
+                    font,
+                    color: Color::WHITE,
+                    font_size: 20.0,
+                },
+            )
+            .with_alignment(TextAlignment::CENTER),
+            // Over the snake, which will be on the same tile.
+            transform: Transform::from_xyz(0.0, 0.0, FOREGROUND_Z + 1.0),
+            ..default()
+        })
+        .insert(GameEntity)
+        .insert(ScorePopup(Timer::from_seconds(SCORE_POPUP_SECONDS, false)))
+        .insert(position)
+        .insert(PositionOffset(Vec2::ZERO));
+}
+
+fn score_popups(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut popups: Query<(Entity, &mut ScorePopup, &mut PositionOffset, &mut Text)>,
+) {
+    for (entity, mut popup, mut offset, mut text) in &mut popups {
+        if popup.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        offset.y = popup.percent() * SCORE_POPUP_RISE;
+        text.sections[0].style.color.set_a(popup.percent_left());
+    }
+}
+
+fn particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut particles: Query<(Entity, &mut Particle, &mut PositionOffset, &mut Sprite)>,
+) {
+    for (entity, mut particle, mut offset, mut sprite) in &mut particles {
+        if particle.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        **offset += particle.velocity * time.delta_seconds();
+        sprite.color.set_a(particle.lifetime.percent_left());
+    }
+}
+
+#[derive(Component, Default)]
+struct LastTailPosition(Option<Position>);
+
+// Sends word the game's won once snakes and walls cover every tile, since
+// there's nothing left to do.
+#[derive(SystemParam)]
+struct FullBoard<'w, 's> {
+    arena: Res<'w, EffectiveArena>,
+    walls: Query<'w, 's, &'static Position, With<Wall>>,
+    game_won_writer: EventWriter<'w, 's, GameWonEvent>,
+}
+
+impl FullBoard<'_, '_> {
+    fn check(&mut self, segments: usize) {
+        let filled = segments
+            + self
+                .walls
+                .iter()
+                .filter(|wall| self.arena.contains(**wall))
+                .count();
+        if filled >= self.arena.area() {
+            self.game_won_writer.send(GameWonEvent);
+        }
+    }
+}
+
+fn snake_growth(
+    mut commands: Commands,
+    mut snakes: Query<(&SnakeHead, &mut SnakeSegments, &LastTailPosition)>,
+    positions: Query<&Position>,
+    mut growth_reader: EventReader<GrowthEvent>,
+    game_state: Res<GameState>,
+    spawner: SnakeSpawner,
+    mut full_board: FullBoard,
+) {
+    if !matches!(*game_state, GameState::Running | GameState::Menu) {
+        return;
+    }
+
+    for growth in growth_reader.iter() {
+        for (head, mut segments, last_tail_position) in &mut snakes {
+            if head.player != growth.player {
+                continue;
+            }
+
+            // Before the snake's first move there's nowhere it's just left, so
+            // stack the new segments on the tail and let them trail out from
+            // there once the snake gets going.
+            //
+            // Growing by more than one stacks them up either way, and they
+            // string out one a tick as each takes the place of the one ahead.
+            let position = last_tail_position.0.or_else(|| {
+                segments
+                    .last()
+                    .and_then(|tail| positions.get(*tail).ok())
+                    .copied()
+            });
+
+            match position {
+                Some(position) => {
+                    segments.extend(
+                        (0..growth.segments)
+                            .map(|_| spawner.spawn_segment(&mut commands, head.player, position)),
+                    );
+                    debug!(
+                        player = head.player,
+                        segments = growth.segments,
+                        length = segments.len(),
+                        "Grew"
+                    );
+                }
+                None => warn!("Player {} has nowhere to grow, skipping", head.player + 1),
+            }
+        }
+    }
+
+    full_board.check(snakes.iter().map(|(_, segments, _)| segments.len()).sum());
+}
+
+// Holds a death back for a moment, so the snakes that died can flash before
+// `game_over` takes a life or ends the run.
+struct DeathFlash {
+    timer: Timer,
+    // Everyone who died, while the flash plays out.
+    losers: Option<HashSet<usize>>,
+}
+
+impl DeathFlash {
+    fn new(seconds: f32) -> Self {
+        Self {
+            timer: Timer::from_seconds(seconds, false),
+            losers: None,
+        }
+    }
+
+    fn flashing(&self) -> bool {
+        self.losers.is_some()
+    }
+
+    // Anyone else who dies while it's playing goes along with the rest. Filling
+    // the board has nobody to flash, so that goes straight through.
+    fn start(&mut self, losers: HashSet<usize>) {
+        if !self.flashing() {
+            self.timer.reset();
+            if losers.is_empty() {
+                self.skip();
+            }
+        }
+        self.losers.get_or_insert_with(HashSet::new).extend(losers);
+    }
+
+    fn skip(&mut self) {
+        let duration = self.timer.duration();
+        self.timer.set_elapsed(duration);
+    }
+
+    fn cancel(&mut self) {
+        self.losers = None;
+    }
+
+    // Everyone who died, once the flash is over.
+    fn finish(&mut self) -> Option<HashSet<usize>> {
+        if self.timer.elapsed() >= self.timer.duration() {
+            self.losers.take()
+        } else {
+            None
+        }
+    }
+
+    // Whether the snakes that died show red right now. They go on and off
+    // `DEATH_FLASHES` times over the flash.
+    fn lit(&self) -> bool {
+        let phase = (self.timer.percent() * DEATH_FLASHES as f32 * 2.0) as u32;
+        self.flashing() && phase.is_multiple_of(2)
+    }
+}
+
+// The snakes' segments, and the colors they'd have when nothing's flashing.
+#[derive(SystemParam)]
+struct SnakeColors<'w, 's> {
+    theme: Res<'w, Theme>,
+    rainbow_skin: Res<'w, RainbowSkin>,
+    snakes: Query<'w, 's, (&'static SnakeHead, &'static SnakeSegments)>,
+    sprites: Query<'w, 's, &'static mut Sprite, With<SnakeSegment>>,
+}
+
+impl SnakeColors<'_, '_> {
+    fn paint(&mut self, death_flash: &DeathFlash) {
+        let palette = self.theme.palette();
+        let lit = death_flash.lit();
+
+        for (head, segments) in &self.snakes {
+            let dying = death_flash
+                .losers
+                .as_ref()
+                .is_some_and(|losers| losers.contains(&head.player));
+
+            for (i, segment) in segments.iter().enumerate() {
+                if let Ok(mut sprite) = self.sprites.get_mut(*segment) {
+                    if dying && lit {
+                        sprite.color = DEATH_FLASH_COLOR;
+                    } else if !**self.rainbow_skin {
+                        sprite.color = palette.snake_segment(head.player, i);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// What plays the flash out: time passing while the game's running, or Space to
+// skip to the end of it.
+#[derive(SystemParam)]
+struct FlashClock<'w, 's> {
+    time: Res<'w, Time>,
+    keyboard: Res<'w, Input<KeyCode>>,
+    game_state: Res<'w, GameState>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl FlashClock<'_, '_> {
+    fn advance(&self, death_flash: &mut DeathFlash) {
+        if *self.game_state != GameState::Running {
+            return;
+        }
+        if self.keyboard.just_pressed(KeyCode::Space) {
+            death_flash.skip();
+        } else {
+            death_flash.timer.tick(self.time.delta());
+        }
+    }
+}
+
+// Plays the flash out and holds everything else still while it does. Space
+// skips it.
+fn death_flash(
+    clock: FlashClock,
+    mut new_run_reader: EventReader<NewRunEvent>,
+    mut death_flash: ResMut<DeathFlash>,
+    mut movement_timer: ResMut<MovementTimer>,
+    mut was_flashing: Local<bool>,
+    mut colors: SnakeColors,
+) {
+    // A restart from the pause menu leaves nobody to flash.
+    if new_run_reader.iter().next().is_some() {
+        death_flash.cancel();
+    }
+
+    // The snakes hold still while it plays out. Only its start and end touch
+    // the timer, so nothing else that pauses it gets unpaused along the way.
+    let flashing = death_flash.flashing();
+    if flashing && !*was_flashing {
+        movement_timer.pause();
+    } else if !flashing && *was_flashing {
+        movement_timer.unpause();
+    }
+
+    if flashing {
+        clock.advance(&mut death_flash);
+    }
+
+    // Once it's over, the theme's colors go back on whatever's left.
+    if !flashing && !*was_flashing {
+        return;
+    }
+    *was_flashing = flashing;
+
+    colors.paint(&death_flash);
+}
+
+#[derive(Default)]
+struct GameOverEvent {
+    // The players who died, if any.
+    losers: Vec<usize>,
+}
+
+#[derive(Component)]
+struct GameOverUi;
+
+// Jolts the camera around for a moment whenever a snake dies, settling back
+// down as the timer runs out. Only the camera moves, so nothing on the board
+// is thrown off.
+struct ScreenShake {
+    timer: Timer,
+    // How far the camera can be thrown from its resting place at first, in
+    // pixels.
+    intensity: f32,
+    // For anyone who'd rather the screen stayed put.
+    enabled: bool,
+}
+
+impl Default for ScreenShake {
+    fn default() -> Self {
+        let mut timer = Timer::from_seconds(SCREEN_SHAKE_SECONDS, false);
+        // Start out settled rather than shaking on startup.
+        timer.tick(timer.duration());
+
+        Self {
+            timer,
+            intensity: SCREEN_SHAKE_INTENSITY,
+            enabled: true,
+        }
+    }
+}
+
+fn toggle_screen_shake(keyboard: Res<Input<KeyCode>>, mut screen_shake: ResMut<ScreenShake>) {
+    if keyboard.just_pressed(KeyCode::F4) {
+        screen_shake.enabled = !screen_shake.enabled;
+    }
+}
+
+fn screen_shake(
+    time: Res<Time>,
+    mut game_over_reader: EventReader<GameOverEvent>,
+    mut screen_shake: ResMut<ScreenShake>,
+    game_state: Res<GameState>,
+    zoom: Res<CameraZoom>,
+    mut cameras: Query<&mut Transform, With<Camera2d>>,
+) {
+    let died = game_over_reader
+        .iter()
+        .any(|event| !event.losers.is_empty());
+    if died && screen_shake.enabled && *game_state != GameState::Menu {
+        screen_shake.timer.reset();
+    }
+
+    // Leave the timer alone once it's run out, so the settings don't look
+    // changed every frame.
+    let offset = if !screen_shake.timer.finished() {
+        screen_shake.timer.tick(time.delta());
+        let strength = screen_shake.intensity * screen_shake.timer.percent_left();
+        // Like particles, this is only for show, so it leaves `GameRng` alone.
+        let mut rng = thread_rng();
+        Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)) * strength
+    } else {
+        Vec2::ZERO
+    };
+
+    for mut transform in &mut cameras {
+        transform.translation.x = zoom.focus.x + offset.x;
+        transform.translation.y = zoom.focus.y + offset.y;
+    }
+}
+
+// Zooms the camera in on player one's head, in steps, with + and - or the
+// scroll wheel, and back out to the whole board with 0. Only the camera
+// changes, so the board underneath stays exactly as it was.
+#[derive(Default)]
+struct CameraZoom {
+    steps: i32,
+    // Where the camera's pointed, in pixels from the middle of the board.
+    focus: Vec2,
+}
+
+impl CameraZoom {
+    fn scale(&self) -> f32 {
+        ZOOM_STEP.powi(-self.steps)
+    }
+}
+
+// The scroll wheel and keys that zoom in and out.
+#[derive(SystemParam)]
+struct ZoomInput<'w, 's> {
+    keyboard: Res<'w, Input<KeyCode>>,
+    wheel_reader: EventReader<'w, 's, MouseWheel>,
+    game_state: Res<'w, GameState>,
+    sandbox: Res<'w, Sandbox>,
+}
+
+impl ZoomInput<'_, '_> {
+    // How many steps in to zoom, going from `steps`.
+    fn steps(&mut self, steps: i32) -> i32 {
+        let keyboard = &self.keyboard;
+        let scrolled = self.wheel_reader.iter().map(|event| event.y).sum::<f32>();
+        // In sandbox mode, + and - resize the arena instead.
+        let zoom_in = scrolled > 0.0
+            || (!**self.sandbox
+                && keyboard.any_just_pressed([KeyCode::Equals, KeyCode::Plus, KeyCode::NumpadAdd]));
+        let zoom_out = scrolled < 0.0
+            || (!**self.sandbox
+                && keyboard.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]));
+
+        // The menu always shows the whole board.
+        if *self.game_state == GameState::Menu
+            || keyboard.any_just_pressed([KeyCode::Key0, KeyCode::Numpad0])
+        {
+            0
+        } else if zoom_in {
+            (steps + 1).min(MAX_ZOOM_STEPS)
+        } else if zoom_out {
+            (steps - 1).max(0)
+        } else {
+            steps
+        }
+    }
+}
+
+fn camera_zoom(
+    mut input: ZoomInput,
+    windows: Res<Windows>,
+    arena: Res<ArenaSize>,
+    mut zoom: ResMut<CameraZoom>,
+    snakes: Query<(&SnakeHead, &Position)>,
+    mut projections: Query<&mut OrthographicProjection, With<Camera2d>>,
+) {
+    let steps = input.steps(zoom.steps);
+
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let (width, height) = (window.width(), window.height());
+
+    // Follows the head from tile to tile, rather than gliding with it, and
+    // stops at the edges so nothing past the board comes into view.
+    let scale = CameraZoom { steps, ..default() }.scale();
+    let focus = snakes
+        .iter()
+        .find(|(head, _)| head.player == 0)
+        .filter(|_| steps > 0)
+        .map_or(Vec2::ZERO, |(_, position)| {
+            let room = Vec2::new(width, height) / 2.0 * (1.0 - scale);
+            Vec2::new(
+                convert_position(position.x as f32, width, arena.width as f32),
+                convert_position(position.y as f32, height, arena.height as f32),
+            )
+            .clamp(-room, room)
+        });
+
+    // Only touched when it moves, so it doesn't look changed every frame.
+    if zoom.steps != steps || zoom.focus != focus {
+        zoom.steps = steps;
+        zoom.focus = focus;
+    }
+
+    for mut projection in &mut projections {
+        if projection.scale != zoom.scale() {
+            projection.scale = zoom.scale();
+        }
+    }
+}
+
+fn game_over(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut deaths: Deaths,
+    mut runs: Runs,
+    recorder: ReplayRecorder,
+    invincible: Res<Invincible>,
+    mut best: BestScores,
+) {
+    let events = deaths.reader.iter().collect::<Vec<_>>();
+    // The demo snake dying is `demo_snake`'s business.
+    if matches!(
+        *runs.state,
+        GameState::Menu | GameState::GameOver | GameState::Won
+    ) {
+        return;
+    }
+
+    for event in &events {
+        debug!(losers = ?event.losers, score = **runs.reset.score, "Game over");
+    }
+
+    if !events.is_empty() {
+        let losers = events
+            .iter()
+            .flat_map(|event| event.losers.iter().copied())
+            .collect::<HashSet<_>>();
+
+        if !deaths.flash.flashing() && !losers.is_empty() {
+            deaths.sound_effects.death();
+        }
+        deaths.flash.start(losers);
+    }
+
+    // Nothing comes of a death until the flash is over.
+    let losers = match deaths.flash.finish() {
+        Some(losers) => losers,
+        None => return,
+    };
+
+    // The game only ends without anyone dying when the board fills up.
+    let won = *runs.mode == GameMode::Single && losers.is_empty();
+
+    // Dying costs a life, and the run carries on with the same score as long
+    // as there are any left. Versus games are over with the first death.
+    if *runs.mode == GameMode::Single && !losers.is_empty() {
+        **runs.reset.lives = runs.reset.lives.saturating_sub(1);
+
+        if **runs.reset.lives > 0 {
+            runs.reset.reset_life(&mut commands, *runs.mode);
+            return;
+        }
+    }
+
+    if recorder.counts(&invincible) {
+        best.record(*runs.mode, &runs.reset.score, &recorder.replay);
+    }
+
+    *runs.state = if won {
+        GameState::Won
+    } else {
+        GameState::GameOver
+    };
+
+    let text_style = TextStyle {
+        font: asset_server.load(FONT_PATH),
+        color: Color::WHITE,
+        font_size: 20.0,
+    };
+
+    commands
+        .spawn_bundle(centered_column())
+        .insert(GameOverUi)
+        .with_children(|parent| {
+            let result = match *runs.mode {
+                GameMode::Single => format!("Score: {}", **runs.reset.score),
+                GameMode::Versus => {
+                    let mut survivors =
+                        (0..runs.mode.players()).filter(|player| !losers.contains(player));
+                    match (survivors.next(), survivors.next()) {
+                        (Some(winner), None) => format!("Player {} Wins!", winner + 1),
+                        _ => "Draw!".to_string(),
+                    }
+                }
+            };
+
+            let title = if won { "You Win!" } else { "Game Over" };
+
+            for line in [title.to_string(), result, "Press R to Restart".to_string()] {
+                parent.spawn_bundle(
+                    TextBundle::from_section(line, text_style.clone()).with_style(Style {
+                        margin: UiRect::all(Val::Px(10.0)),
+                        ..default()
+                    }),
+                );
+            }
+        });
+
+    // The final score's up on the screen, so the next run can start from a
+    // clean slate behind it.
+    runs.reset.reset_run(&mut commands, *runs.mode);
+}
+
+// Snakes dying, which flashes for a moment before anything comes of it.
+#[derive(SystemParam)]
+struct Deaths<'w, 's> {
+    reader: EventReader<'w, 's, GameOverEvent>,
+    flash: ResMut<'w, DeathFlash>,
+    sound_effects: SoundEffects<'w, 's>,
+}
+
+fn restart(
+    mut commands: Commands,
+    mut requests: RestartRequests,
+    mut runs: Runs,
+    replay: Res<Replay>,
+    mut replay_mode: ResMut<ReplayMode>,
+    mut options: RunOptions,
+    mut best: BestScores,
+) {
+    if !requests.requested() {
+        return;
+    }
+
+    match *runs.state {
+        GameState::GameOver | GameState::Won => {}
+        // Starting over mid-run skips the game over screen, but the run still
+        // counts towards the high score.
+        GameState::Running | GameState::Paused => {
+            if *replay_mode == ReplayMode::Record && !**options.invincible {
+                best.record(*runs.mode, &runs.reset.score, &replay);
+            }
+        }
+        _ => return,
+    }
+
+    // Watching a replay ends with it, and restarting goes back to playing, by
+    // the player's own options unless it's the daily challenge again.
+    *replay_mode = ReplayMode::Record;
+    if !best.daily.active {
+        options.put_back();
+    }
+
+    runs.start(&mut commands);
+}
+
+// R, or Restart picked from the pause menu.
+#[derive(SystemParam)]
+struct RestartRequests<'w, 's> {
+    keyboard: Res<'w, Input<KeyCode>>,
+    reader: EventReader<'w, 's, RestartEvent>,
+}
+
+impl RestartRequests<'_, '_> {
+    fn requested(&mut self) -> bool {
+        let picked = self.reader.iter().count() > 0;
+        picked || self.keyboard.just_pressed(KeyCode::R)
+    }
+}
+
+// Where the game's at, and what it takes to get a fresh run going.
+#[derive(SystemParam)]
+struct Runs<'w, 's> {
+    state: ResMut<'w, GameState>,
+    mode: ResMut<'w, GameMode>,
+    new_run_writer: EventWriter<'w, 's, NewRunEvent>,
+    reset: RunReset<'w, 's>,
+    screens: Query<'w, 's, Entity, Or<(With<MenuUi>, With<GameOverUi>)>>,
+}
+
+impl Runs<'_, '_> {
+    // Clears away whatever screen's up, along with anything left of the last
+    // run, and starts a new one.
+    fn start(&mut self, commands: &mut Commands) {
+        for entity in &self.screens {
+            commands.entity(entity).despawn_recursive();
+        }
+
+        *self.state = GameState::Running;
+        self.new_run_writer.send(NewRunEvent);
+        self.reset.reset_run(commands, *self.mode);
+    }
+}
+
+// The scores a run that counts goes towards when it's over: the daily
+// challenge's best when that's what's being played, the high score otherwise.
+#[derive(SystemParam)]
+struct BestScores<'w, 's> {
+    high_score: ResMut<'w, HighScore>,
+    daily: ResMut<'w, DailyChallenge>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl BestScores<'_, '_> {
+    fn record(&mut self, game_mode: GameMode, score: &Score, replay: &Replay) {
+        if self.daily.active {
+            self.daily.record(**score);
+        } else if record_high_score(game_mode, score, &mut self.high_score) {
+            save_replay(replay);
+        }
+    }
+}
+
+// What's kept track of over a run and starts over with the next one. The high
+// score, achievements and settings all carry on from one run to the next, so
+// they're kept well away from here.
+#[derive(SystemParam)]
+struct RunReset<'w, 's> {
+    score: ResMut<'w, Score>,
+    combo: ResMut<'w, Combo>,
+    play_time: ResMut<'w, PlayTime>,
+    lives: ResMut<'w, Lives>,
+    countdown: ResMut<'w, Countdown>,
+    effective: ResMut<'w, EffectiveArena>,
+    spawner: SnakeSpawner<'w, 's>,
+    board: Query<'w, 's, Entity, With<GameEntity>>,
+}
+
+impl RunReset<'_, '_> {
+    // Clears the board and puts fresh snakes on it after a countdown, for a
+    // new life. The score and everything else about the run stays as it was.
+    fn reset_life(&mut self, commands: &mut Commands, game_mode: GameMode) {
+        for entity in &self.board {
+            commands.entity(entity).despawn_recursive();
+        }
+
+        // The arena opens back up, so the snake has somewhere to start.
+        *self.effective = EffectiveArena::from(&*self.spawner.arena);
+        self.countdown.reset();
+        self.spawner.spawn_snakes(commands, game_mode);
+    }
+
+    fn reset_run(&mut self, commands: &mut Commands, game_mode: GameMode) {
+        self.reset_life(commands, game_mode);
+
+        *self.score = Score::default();
+        *self.combo = Combo::default();
+        self.play_time.reset();
+        *self.lives = Lives::default();
+    }
+}
+
+struct GameWonEvent;
+
+fn game_won(
+    mut reader: EventReader<GameWonEvent>,
+    mut game_over_writer: EventWriter<GameOverEvent>,
+) {
+    if reader.iter().next().is_some() {
+        info!("You win!");
+        // Reuse the regular reset path so the score still counts towards the
+        // high score.
+        game_over_writer.send(GameOverEvent::default());
+    }
+}
+
+// Counts movement ticks since the run started. Anything that affects how the
+// game plays out follows this clock, advancing by the tick's interval, rather
+// than the wall clock.
+#[derive(Default)]
+struct MovementTick {
+    count: u64,
+    interval: Duration,
+    // Every tick's interval so far added up, which is how long the run's been
+    // going by this clock.
+    elapsed: Duration,
+}
+
+// How many pieces of normal food there should be on the board at once.
+#[derive(Deref, DerefMut)]
+struct FoodCount(usize);
+
+impl Default for FoodCount {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+// When eaten food comes back. `Instant` puts it straight back down somewhere
+// else as it's eaten, and `Timed` waits for the next spawn, which comes around
+// every so many seconds.
+#[derive(Clone, Copy, PartialEq)]
+enum FoodRespawn {
+    Instant,
+    Timed(f32),
+}
+
+impl Default for FoodRespawn {
+    fn default() -> Self {
+        Self::Timed(FOOD_SPAWN_INTERVAL)
+    }
+}
+
+// Everything else still turns up on a timer in `Instant` mode, as does any
+// food that's been cleared away.
+#[derive(Deref, DerefMut)]
+struct FoodTimer(Timer);
+
+impl FoodTimer {
+    fn new(respawn: FoodRespawn) -> Self {
+        let interval = match respawn {
+            FoodRespawn::Instant => FOOD_SPAWN_INTERVAL,
+            FoodRespawn::Timed(interval) => interval,
+        };
+        Self(Timer::from_seconds(interval, true))
+    }
+}
+
+impl Default for FoodTimer {
+    fn default() -> Self {
+        Self::new(FoodRespawn::default())
+    }
+}
+
+// What `snake_eating` needs to put food back down in `Instant` mode.
+#[derive(SystemParam)]
+struct FoodRespawner<'w, 's> {
+    respawn: Res<'w, FoodRespawn>,
+    arena: Res<'w, EffectiveArena>,
+    mask: Res<'w, ArenaMask>,
+    theme: Res<'w, Theme>,
+    rng: ResMut<'w, GameRng>,
+    taken: Query<
+        'w,
+        's,
+        (&'static Position, Option<&'static BigFood>),
+        Or<(With<SnakeSegment>, With<Wall>, With<Food>, With<Portal>)>,
+    >,
+}
+
+impl FoodRespawner<'_, '_> {
+    // Keeps clear of everything on the board and anything in `spawned`, which
+    // is where food's already been put back this tick, since none of it is on
+    // the board yet.
+    fn respawn(&mut self, commands: &mut Commands, spawned: &mut Vec<Position>) {
+        if *self.respawn != FoodRespawn::Instant {
+            return;
+        }
+
+        let occupied = self
+            .taken
+            .iter()
+            .flat_map(|(position, big)| food_tiles(*position, big))
+            .chain(spawned.iter().copied())
+            .collect::<HashSet<_>>();
+        // A full board is `food_spawner`'s to notice.
+        if let Some(position) =
+            random_free_position(&self.arena, &self.mask, &occupied, &mut **self.rng)
+        {
+            spawn_food(commands, position, FoodKind::Normal, &self.theme.palette());
+            spawned.push(position);
+        }
+    }
+}
+
+// Where all of the game's randomness comes from, seeded per run so a replay
+// can reproduce it. The game starts out on `SNAKE_SEED`, or `DEFAULT_SEED`
+// without it.
+#[derive(Deref, DerefMut)]
+struct GameRng(StdRng);
+
+impl GameRng {
+    fn from_env() -> Self {
+        Self(StdRng::seed_from_u64(env_seed().unwrap_or(DEFAULT_SEED)))
+    }
+}
+
+// Setting SNAKE_SEED pins every run to the same seed, so food turns up in the
+// same places each time.
+fn env_seed() -> Option<u64> {
+    let seed = std::env::var(SEED_VAR).ok()?;
+
+    seed.parse()
+        .map_err(|err| warn!("Ignoring {}={:?}: {}", SEED_VAR, seed, err))
+        .ok()
+}
+
+// Everything needed to play a single player run back: the seed its food was
+// spawned from and every turn it made, keyed by the tick it happened on.
+//
+// Playing one back re-simulates the run, so it only comes out the same
+// because food and movement both follow `MovementTick` and all randomness
+// comes from `GameRng`. Anything that feeds the wall clock or another source
+// of randomness into the game breaks that.
+#[derive(Default, Serialize, Deserialize)]
+struct Replay {
+    seed: u64,
+    #[serde(default)]
+    difficulty: Difficulty,
+    #[serde(default)]
+    shrinking: bool,
+    #[serde(default)]
+    invincible: bool,
+    #[serde(default)]
+    shape: ArenaShape,
+    #[serde(default)]
+    roaming_enemy: bool,
+    #[serde(default)]
+    speed_ramp: bool,
+    #[serde(default)]
+    wrap_mode: WrapMode,
+    inputs: Vec<(u64, Direction)>,
+    // The ticks the panic button was pressed after.
+    #[serde(default)]
+    panics: Vec<u64>,
+}
+
+// Everything picked on the menu that changes how a run plays out, which a
+// replay has to have the same to play out the same.
+#[derive(SystemParam)]
+struct RunOptions<'w, 's> {
+    difficulty: ResMut<'w, Difficulty>,
+    shrink: ResMut<'w, ArenaShrink>,
+    invincible: ResMut<'w, Invincible>,
+    shape: ResMut<'w, ArenaShape>,
+    roaming_enemy: ResMut<'w, RoamingEnemy>,
+    speed_ramp: ResMut<'w, SpeedRamp>,
+    wrap_mode: ResMut<'w, WrapMode>,
+    player: ResMut<'w, PlayerOptions>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl RunOptions<'_, '_> {
+    // A fresh recording of a run with these options.
+    fn replay(&self, seed: u64) -> Replay {
+        Replay {
+            seed,
+            difficulty: *self.difficulty,
+            shrinking: self.shrink.enabled,
+            invincible: **self.invincible,
+            shape: *self.shape,
+            roaming_enemy: **self.roaming_enemy,
+            speed_ramp: self.speed_ramp.enabled,
+            wrap_mode: *self.wrap_mode,
+            inputs: Vec::new(),
+            panics: Vec::new(),
+        }
+    }
+
+    fn restore(&mut self, replay: &Replay) {
+        *self.difficulty = replay.difficulty;
+        self.shrink.enabled = replay.shrinking;
+        **self.invincible = replay.invincible;
+        *self.shape = replay.shape;
+        **self.roaming_enemy = replay.roaming_enemy;
+        self.speed_ramp.enabled = replay.speed_ramp;
+        *self.wrap_mode = replay.wrap_mode;
+    }
+
+    // Plays by someone else's options until `put_back`, keeping the player's
+    // own aside.
+    fn play_by(&mut self, replay: &Replay) {
+        if self.player.is_none() {
+            **self.player = Some(self.replay(0));
+        }
+        self.restore(replay);
+    }
+
+    fn put_back(&mut self) {
+        if let Some(player) = self.player.take() {
+            self.restore(&player);
+        }
+    }
+}
+
+// The options the player picked for themselves, while a run's playing by
+// another set. They're what's kept in the settings in the meantime.
+#[derive(Default, Deref, DerefMut)]
+struct PlayerOptions(Option<Replay>);
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum ReplayMode {
+    #[default]
+    Record,
+    Playback {
+        // Index into `Replay::inputs` of the next turn to make.
+        next_input: usize,
+        // And into `Replay::panics`.
+        next_panic: usize,
+    },
+    // A run picked up from a snapshot, which can't be played back from the
+    // start, and so doesn't count towards the best run or the high score.
+    Off,
+}
+
+// Writes turns into the replay, unless one's being played back.
+#[derive(SystemParam)]
+struct ReplayRecorder<'w, 's> {
+    replay: ResMut<'w, Replay>,
+    mode: Res<'w, ReplayMode>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl ReplayRecorder<'_, '_> {
+    // Whether the run counts towards the high score and the best run, which
+    // practice runs never do.
+    fn counts(&self, invincible: &Invincible) -> bool {
+        *self.mode == ReplayMode::Record && !**invincible
+    }
+
+    fn record(&mut self, tick: u64, turn: Direction) {
+        if *self.mode == ReplayMode::Record {
+            self.replay.inputs.push((tick, turn));
+        }
+    }
+}
+
+// Sent whenever a fresh run starts, as opposed to losing a life.
+struct NewRunEvent;
+
+// The best run on record, played back in step with each single player run to
+// race against. It's a whole game of its own, so it can't touch this one.
+#[derive(Default)]
+struct Ghost(Option<Simulation>);
+
+#[derive(Component)]
+struct GhostSegment;
+
+// What sort of run's going, which decides whether there's a ghost to race.
+#[derive(SystemParam)]
+struct GhostRace<'w, 's> {
+    game_state: Res<'w, GameState>,
+    game_mode: Res<'w, GameMode>,
+    replay_mode: Res<'w, ReplayMode>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl GhostRace<'_, '_> {
+    // Watching the best run play back against itself would be a bit much.
+    fn wanted(&self) -> bool {
+        *self.game_mode == GameMode::Single && *self.replay_mode == ReplayMode::Record
+    }
+
+    // A run loaded from a snapshot has nothing to race.
+    fn on(&self) -> bool {
+        matches!(*self.game_state, GameState::Running | GameState::Paused)
+            && *self.replay_mode != ReplayMode::Off
+    }
+}
+
+fn ghost(
+    mut commands: Commands,
+    mut ghost: NonSendMut<Ghost>,
+    mut new_run_reader: EventReader<NewRunEvent>,
+    tick: Res<MovementTick>,
+    race: GhostRace,
+    mut segments: Query<(Entity, &mut Position), With<GhostSegment>>,
+) {
+    if new_run_reader.iter().next().is_some() {
+        ghost.0 = race.wanted().then(Simulation::best_run).flatten();
+    }
+
+    let run = match &mut ghost.0 {
+        Some(run) if race.on() => run,
+        _ => {
+            for (entity, _) in &segments {
+                commands.entity(entity).despawn();
+            }
+            return;
+        }
+    };
+
+    // The ghost moves whenever the live snake does, once it's got going.
+    if tick.is_changed() && tick.count > 0 {
+        run.step();
+    }
+
+    // It stays where it ended for as long as the live run carries on.
+    let positions = run.snake(0);
+    if positions.len() == segments.iter().count() {
+        for ((_, mut position), ghost_position) in segments.iter_mut().zip(positions) {
+            *position = Position {
+                x: ghost_position.x,
+                y: ghost_position.y,
+            };
+        }
+        return;
+    }
+
+    for (entity, _) in &segments {
+        commands.entity(entity).despawn();
+    }
+    for ghost_position in positions {
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color: GHOST_COLOR,
+                    ..default()
+                },
+                transform: Transform::from_xyz(0.0, 0.0, MARKER_Z),
+                ..default()
+            })
+            .insert(GhostSegment)
+            .insert(Position {
+                x: ghost_position.x,
+                y: ghost_position.y,
+            })
+            .insert(Size::square(0.65));
+    }
+}
+
+// Where a run's randomness comes from, and the replay that keeps its seed.
+#[derive(SystemParam)]
+struct RunSeed<'w, 's> {
+    rng: ResMut<'w, GameRng>,
+    replay: ResMut<'w, Replay>,
+    replay_mode: Res<'w, ReplayMode>,
+    daily: Res<'w, DailyChallenge>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+// Everything that keeps time over a run, all started over for a new one.
+#[derive(SystemParam)]
+struct RunClocks<'w, 's> {
+    tick: ResMut<'w, MovementTick>,
+    movement_timer: ResMut<'w, MovementTimer>,
+    food_timer: ResMut<'w, FoodTimer>,
+    food_respawn: Res<'w, FoodRespawn>,
+    power_ups: PowerUps<'w, 's>,
+    panic_cooldown: ResMut<'w, PanicCooldown>,
+}
+
+fn start_recording(
+    mut reader: EventReader<NewRunEvent>,
+    mut seed: RunSeed,
+    mut clocks: RunClocks,
+    arena: Res<ArenaSize>,
+    mut effective: ResMut<EffectiveArena>,
+    mut options: RunOptions,
+) {
+    if reader.iter().next().is_none() {
+        return;
+    }
+
+    if *seed.replay_mode == ReplayMode::Record {
+        // Without a seed to go by, each run's is drawn from the last, so they
+        // all follow on from the one the game started with.
+        let run_seed = seed
+            .daily
+            .seed()
+            .or_else(env_seed)
+            .unwrap_or_else(|| seed.rng.gen());
+        *seed.replay = options.replay(run_seed);
+    }
+
+    *clocks.movement_timer = MovementTimer::new(*options.difficulty);
+    options.speed_ramp.base = options.difficulty.base_interval();
+    *clocks.power_ups.slow_mo = SlowMo::default();
+    *clocks.power_ups.freeze = FreezeTimer::default();
+    *clocks.power_ups.magnet = Magnet::default();
+    *clocks.panic_cooldown = PanicCooldown::default();
+    *effective = EffectiveArena::from(&*arena);
+    options.shrink.timer.reset();
+    *clocks.tick = MovementTick::default();
+    *clocks.food_timer = FoodTimer::new(*clocks.food_respawn);
+    **seed.rng = StdRng::seed_from_u64(seed.replay.seed);
+}
+
+fn replay_playback(
+    tick: Res<MovementTick>,
+    replay: Res<Replay>,
+    mut replay_mode: ResMut<ReplayMode>,
+    mut heads: Query<&mut SnakeHead>,
+) {
+    if let ReplayMode::Playback { next_input, .. } = &mut *replay_mode {
+        // Queue each turn up just before the tick it was made on.
+        if let Some(&(input_tick, direction)) = replay.inputs.get(*next_input) {
+            if input_tick == tick.count {
+                for mut head in &mut heads {
+                    head.input_buffer.clear();
+                    head.input_buffer.push_back(direction);
+                }
+                *next_input += 1;
+            }
+        }
+    }
+}
+
+// Gets a single player out of a tight spot by putting the head straight in the
+// middle of the arena, with the body following it there like it went through a
+// portal. It's no help on Hard, with the arena shrinking or in the daily
+// challenge, which are meant to be played straight, and it won't drop the head
+// onto anything.
+#[derive(Deref, DerefMut)]
+struct PanicCooldown(Timer);
+
+impl Default for PanicCooldown {
+    fn default() -> Self {
+        let mut timer = Timer::from_seconds(PANIC_COOLDOWN_SECONDS, false);
+        // Every run starts with it ready to go.
+        timer.tick(timer.duration());
+        Self(timer)
+    }
+}
+
+#[derive(Component)]
+struct PanicText;
+
+// Whatever decides whether the panic button can be used at all.
+#[derive(SystemParam)]
+struct PanicRules<'w, 's> {
+    game_mode: Res<'w, GameMode>,
+    difficulty: Res<'w, Difficulty>,
+    shrink: Res<'w, ArenaShrink>,
+    daily: Res<'w, DailyChallenge>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl PanicRules<'_, '_> {
+    fn available(&self) -> bool {
+        *self.game_mode == GameMode::Single
+            && *self.difficulty != Difficulty::Hard
+            && !self.shrink.enabled
+            && !self.daily.active
+    }
+}
+
+// Whether the panic button's been pressed, either just now or at this point in
+// the run being played back, and what's kept of it going off.
+#[derive(SystemParam)]
+struct PanicTrigger<'w, 's> {
+    keyboard: Res<'w, Input<KeyCode>>,
+    game_state: Res<'w, GameState>,
+    countdown: Res<'w, Countdown>,
+    death_flash: Res<'w, DeathFlash>,
+    rules: PanicRules<'w, 's>,
+    tick: Res<'w, MovementTick>,
+    replay: ResMut<'w, Replay>,
+    replay_mode: ResMut<'w, ReplayMode>,
+    cooldown: ResMut<'w, PanicCooldown>,
+}
+
+impl PanicTrigger<'_, '_> {
+    // Press it whenever and it goes off between ticks, and the replay keeps
+    // the tick it went off after so playing it back does it at the same point.
+    fn pressed(&mut self) -> bool {
+        if *self.game_state != GameState::Running || !self.game_state.simulating(&self.countdown) {
+            return false;
+        }
+        if self.tick.is_changed() {
+            self.cooldown.tick(self.tick.interval);
+        }
+
+        match &mut *self.replay_mode {
+            ReplayMode::Playback { next_panic, .. } => {
+                let pressed = self.replay.panics.get(*next_panic) == Some(&self.tick.count);
+                if pressed {
+                    *next_panic += 1;
+                }
+                pressed
+            }
+            _ => {
+                self.keyboard.just_pressed(KeyCode::Space)
+                    && !self.death_flash.flashing()
+                    && self.cooldown.finished()
+                    && self.rules.available()
+            }
+        }
+    }
+
+    fn went_off(&mut self) {
+        self.cooldown.reset();
+        if *self.replay_mode == ReplayMode::Record {
+            self.replay.panics.push(self.tick.count);
+        }
+    }
+}
+
+fn panic_button(
+    mut trigger: PanicTrigger,
+    arena: Res<EffectiveArena>,
+    mask: Res<ArenaMask>,
+    mut heads: Query<&mut Position, With<SnakeHead>>,
+    blocked: Query<
+        (&Position, Option<&BigFood>),
+        (
+            Or<(
+                With<SnakeSegment>,
+                With<Wall>,
+                With<Food>,
+                With<Portal>,
+                With<Enemy>,
+            )>,
+            Without<SnakeHead>,
+        ),
+    >,
+) {
+    if !trigger.pressed() {
+        return;
+    }
+
+    let centre = Position {
+        x: arena.left + arena.width as i32 / 2,
+        y: arena.bottom + arena.height as i32 / 2,
+    };
+    let taken = heads.iter().any(|head| *head == centre)
+        || blocked
+            .iter()
+            .any(|(position, big)| food_tiles(*position, big).contains(&centre));
+    if taken || !mask.contains(&centre) {
+        return;
+    }
+
+    for mut head in &mut heads {
+        *head = centre;
+    }
+    trigger.went_off();
+}
+
+fn panic_text(
+    game_state: Res<GameState>,
+    rules: PanicRules,
+    replay_mode: Res<ReplayMode>,
+    cooldown: Res<PanicCooldown>,
+    mut texts: Query<(&mut Text, &mut Visibility), With<PanicText>>,
+) {
+    for (mut text, mut visibility) in &mut texts {
+        visibility.is_visible = *game_state != GameState::Menu
+            && !matches!(*replay_mode, ReplayMode::Playback { .. })
+            && rules.available();
+        if !visibility.is_visible {
+            continue;
+        }
+
+        let value = if cooldown.finished() {
+            "Panic: Ready (Space)".to_string()
+        } else {
+            let remaining = cooldown.duration() - cooldown.elapsed();
+            format!("Panic: {}s", remaining.as_secs() + 1)
+        };
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+// Somewhere to keep the few things that outlive a launch, keyed by the same
+// paths either way. Files next to the game natively, and the browser's local
+// storage on the web, where there's no filesystem to speak of.
+trait Storage {
+    // A missing key is an `io::ErrorKind::NotFound` on every backend.
+    fn read(&self, key: &str) -> io::Result<String>;
+    fn write(&self, key: &str, contents: &str) -> io::Result<()>;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct FileStorage;
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Storage for FileStorage {
+    fn read(&self, key: &str) -> io::Result<String> {
+        std::fs::read_to_string(key)
+    }
+
+    fn write(&self, key: &str, contents: &str) -> io::Result<()> {
+        std::fs::write(key, contents)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+struct LocalStorage;
+
+#[cfg(target_arch = "wasm32")]
+impl LocalStorage {
+    fn storage(&self) -> io::Result<web_sys::Storage> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "no local storage"))
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Storage for LocalStorage {
+    fn read(&self, key: &str) -> io::Result<String> {
+        self.storage()?
+            .get_item(key)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{:?}", err)))?
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+
+    fn write(&self, key: &str, contents: &str) -> io::Result<()> {
+        self.storage()?
+            .set_item(key, contents)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, format!("{:?}", err)))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn storage() -> impl Storage {
+    FileStorage
+}
+
+#[cfg(target_arch = "wasm32")]
+fn storage() -> impl Storage {
+    LocalStorage
+}
+
+fn load_replay() -> Option<Replay> {
+    let contents = storage()
+        .read(REPLAY_PATH)
+        .map_err(|err| warn!("Couldn't read {}: {}", REPLAY_PATH, err))
+        .ok()?;
+
+    ron::from_str(&contents)
+        .map_err(|err| warn!("Couldn't parse {}: {}", REPLAY_PATH, err))
+        .ok()
+}
+
+fn save_replay(replay: &Replay) {
+    let result = ron::to_string(replay)
+        .map_err(|err| err.to_string())
+        .and_then(|contents| {
+            storage()
+                .write(REPLAY_PATH, &contents)
+                .map_err(|err| err.to_string())
+        });
+
+    if let Err(err) = result {
+        warn!("Couldn't save replay to {}: {}", REPLAY_PATH, err);
+    }
+}
+
+#[derive(Default, Deref, DerefMut)]
+struct Score(usize);
+
+#[derive(Component)]
+struct ScoreText;
+
+fn score_text(score: Res<Score>, combo: Res<Combo>, mut texts: Query<&mut Text, With<ScoreText>>) {
+    for mut text in &mut texts {
+        text.sections[0].value = if combo.multiplier > 1 {
+            format!("Score: {} x{}", **score, combo.multiplier)
+        } else {
+            format!("Score: {}", **score)
+        };
+    }
+}
+
+// Puts the score in the window's title while there's a run going, for when the
+// window's too small to read it or the game's being streamed.
+fn window_title(
+    mut windows: ResMut<Windows>,
+    game_state: Res<GameState>,
+    score: Res<Score>,
+    high_score: Res<HighScore>,
+) {
+    if !(game_state.is_changed() || score.is_changed() || high_score.is_changed()) {
+        return;
+    }
+
+    let window = match windows.get_primary_mut() {
+        Some(window) => window,
+        None => return,
+    };
+
+    let title = if *game_state == GameState::Menu {
+        WINDOW_TITLE.to_string()
+    } else {
+        format!(
+            "{} \u{2014} Score: {} \u{2014} Best: {}",
+            WINDOW_TITLE, **score, **high_score
+        )
+    };
+
+    // Every state change counts as a change, even one from paused back to
+    // running, so check before touching the window.
+    if window.title() != title {
+        window.set_title(title);
+    }
+}
+
+// Multiplies the points for food eaten in quick succession.
+struct Combo {
+    multiplier: u32,
+    timer: Timer,
+}
+
+impl Combo {
+    // Counts another piece of food towards the combo, returning the multiplier
+    // it's worth.
+    fn bump(&mut self) -> u32 {
+        self.multiplier = if self.timer.finished() {
+            1
+        } else {
+            (self.multiplier + 1).min(MAX_COMBO)
+        };
+        self.timer.reset();
+        self.multiplier
+    }
+}
+
+impl Default for Combo {
+    fn default() -> Self {
+        let mut timer = Timer::from_seconds(COMBO_WINDOW, false);
+        // There's no combo going until the first piece of food.
+        timer.tick(timer.duration());
+
+        Self {
+            multiplier: 1,
+            timer,
+        }
+    }
+}
+
+// Runs on the snake's clock like the food does, so replays score the same.
+fn combo_decay(tick: Res<MovementTick>, mut combo: ResMut<Combo>) {
+    if !tick.is_changed() || combo.timer.finished() {
+        return;
+    }
+
+    if combo.timer.tick(tick.interval).finished() {
+        combo.multiplier = 1;
+    }
+}
+
+// Everything needed to pick a run back up where it was left off, saved with F5
+// and loaded with F9.
+//
+// Shrinking, slow-mo, portals and every other timed effect bar a freeze are left
+// out, and just start over.
+#[derive(Serialize, Deserialize)]
+struct GameSnapshot {
+    version: u32,
+    game_mode: GameMode,
+    difficulty: Difficulty,
+    wrap_mode: WrapMode,
+    arena: ArenaSize,
+    #[serde(default)]
+    shape: ArenaShape,
+    score: usize,
+    lives: u32,
+    tick: u64,
+    // How long the run had been going by the snake's clock, which the speed
+    // ramp goes by.
+    #[serde(default)]
+    elapsed: f32,
+    movement_interval: f32,
+    // How much longer the snakes were being held still for.
+    #[serde(default)]
+    frozen: f32,
+    snakes: Vec<SnakeSnapshot>,
+    food: Vec<FoodSnapshot>,
+}
+
+impl GameSnapshot {
+    // Whatever's wrong with a snapshot that can't be played from, as a
+    // hand-edited or corrupted one could be.
+    fn problem(&self) -> Option<&'static str> {
+        let sizes = MIN_ARENA_SIZE..=MAX_ARENA_SIZE;
+        if !sizes.contains(&self.arena.width) || !sizes.contains(&self.arena.height) {
+            return Some("its arena is the wrong size");
+        }
+
+        if Duration::try_from_secs_f32(self.elapsed).is_err() {
+            return Some("it's been going for a length of time there's no such thing as");
+        }
+
+        if !(0.0..=FREEZE_SECONDS).contains(&self.frozen) {
+            return Some("it's frozen for longer than freeze food lasts");
+        }
+
+        if self
+            .food
+            .iter()
+            .any(|food| !(0.0..=BONUS_FOOD_LIFETIME).contains(&food.elapsed))
+        {
+            return Some("it has food that's been out for longer than it can be");
+        }
+
+        if self
+            .snakes
+            .iter()
+            .any(|snake| snake.player >= SNAKE_STARTS.len())
+        {
+            return Some("it has a snake for a player that doesn't exist");
+        }
+
+        if self.snakes.iter().any(|snake| snake.segments.is_empty()) {
+            return Some("it has a snake with no segments");
+        }
+
+        let arena = EffectiveArena::from(&self.arena);
+        let segments = self.snakes.iter().flat_map(|snake| snake.segments.clone());
+        let food = self.food.iter().flat_map(|food| match food.kind {
+            FoodKind::Big => BigFood::new(food.position).tiles,
+            _ => vec![food.position],
+        });
+        if !segments
+            .chain(food)
+            .all(|position| arena.contains(position))
+        {
+            return Some("it has something outside the arena");
+        }
+
+        None
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnakeSnapshot {
+    player: usize,
+    direction: Direction,
+    // Head first, same as `SnakeSegments`.
+    segments: Vec<Position>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FoodSnapshot {
+    // Big food's is its bottom-left tile.
+    position: Position,
+    kind: FoodKind,
+    // How long bonus food had been out for.
+    elapsed: f32,
+}
+
+// The resources a snapshot saves and restores, bar the board itself.
+#[derive(SystemParam)]
+struct RunState<'w, 's> {
+    game_mode: ResMut<'w, GameMode>,
+    difficulty: ResMut<'w, Difficulty>,
+    wrap_mode: ResMut<'w, WrapMode>,
+    shape: ResMut<'w, ArenaShape>,
+    effective: ResMut<'w, EffectiveArena>,
+    score: ResMut<'w, Score>,
+    lives: ResMut<'w, Lives>,
+    tick: ResMut<'w, MovementTick>,
+    movement_timer: ResMut<'w, MovementTimer>,
+    death_flash: ResMut<'w, DeathFlash>,
+    freeze: ResMut<'w, FreezeTimer>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+fn save_snapshot(
+    keyboard: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    run: RunState,
+    arena: Res<ArenaSize>,
+    heads: Query<(&SnakeHead, &SnakeSegments)>,
+    positions: Query<&Position>,
+    food: Query<(&Position, &FoodKind, Option<&BonusFood>), With<Food>>,
+) {
+    if !keyboard.just_pressed(KeyCode::F5)
+        || !matches!(*game_state, GameState::Running | GameState::Paused)
+    {
+        return;
+    }
+
+    let snakes = heads
+        .iter()
+        .map(|(head, segments)| SnakeSnapshot {
+            player: head.player,
+            // Any turns still queued up are dropped, so the snake picks up
+            // going the way it actually was.
+            direction: head.direction,
+            segments: segments
+                .iter()
+                .filter_map(|segment| positions.get(*segment).ok())
+                .copied()
+                .collect(),
+        })
+        .collect();
+
+    let food = food
+        .iter()
+        .map(|(position, kind, bonus)| FoodSnapshot {
+            position: *position,
+            kind: *kind,
+            elapsed: bonus.map_or(0.0, |bonus| bonus.elapsed_secs()),
+        })
+        .collect();
+
+    let snapshot = GameSnapshot {
+        version: SNAPSHOT_VERSION,
+        game_mode: *run.game_mode,
+        difficulty: *run.difficulty,
+        wrap_mode: *run.wrap_mode,
+        arena: *arena,
+        shape: *run.shape,
+        score: **run.score,
+        lives: **run.lives,
+        tick: run.tick.count,
+        elapsed: run.tick.elapsed.as_secs_f32(),
+        movement_interval: run.movement_timer.duration().as_secs_f32(),
+        frozen: run.freeze.duration().as_secs_f32() - run.freeze.elapsed_secs(),
+        snakes,
+        food,
+    };
+
+    let result = ron::to_string(&snapshot)
+        .map_err(|err| err.to_string())
+        .and_then(|contents| {
+            storage()
+                .write(SNAPSHOT_PATH, &contents)
+                .map_err(|err| err.to_string())
+        });
+
+    match result {
+        Ok(()) => info!("Saved the game to {}", SNAPSHOT_PATH),
+        Err(err) => warn!("Couldn't save the game to {}: {}", SNAPSHOT_PATH, err),
+    }
+}
+
+// How a loaded game picks up, which isn't anything a snapshot keeps.
+#[derive(SystemParam)]
+struct Resume<'w, 's> {
+    game_state: ResMut<'w, GameState>,
+    countdown: ResMut<'w, Countdown>,
+    replay_mode: ResMut<'w, ReplayMode>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl Resume<'_, '_> {
+    // Paused, to give the player a moment before the countdown.
+    fn paused(&mut self) {
+        *self.replay_mode = ReplayMode::Off;
+        *self.game_state = GameState::Paused;
+        self.countdown.reset();
+    }
+}
+
+fn load_snapshot(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    mut run: RunState,
+    mut resume: Resume,
+    spawner: SnakeSpawner,
+    board: Query<Entity, Or<(With<GameEntity>, With<MenuUi>, With<GameOverUi>)>>,
+) {
+    if !keyboard.just_pressed(KeyCode::F9) {
+        return;
+    }
+
+    let snapshot = match storage().read(SNAPSHOT_PATH).map_err(|err| err.to_string()) {
+        Ok(contents) => ron::from_str::<GameSnapshot>(&contents).map_err(|err| err.to_string()),
+        Err(err) => Err(err),
+    };
+    let snapshot = match snapshot {
+        Ok(snapshot) if snapshot.version == SNAPSHOT_VERSION => snapshot,
+        Ok(snapshot) => {
+            warn!(
+                "Not loading {}, it's from version {} but this is version {}",
+                SNAPSHOT_PATH, snapshot.version, SNAPSHOT_VERSION
+            );
+            return;
+        }
+        Err(err) => {
+            warn!("Couldn't load the game from {}: {}", SNAPSHOT_PATH, err);
+            return;
+        }
+    };
+
+    if let Some(problem) = snapshot.problem() {
+        warn!("Not loading {}, {}", SNAPSHOT_PATH, problem);
+        return;
+    }
+
+    for entity in &board {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    *run.game_mode = snapshot.game_mode;
+    *run.difficulty = snapshot.difficulty;
+    *run.wrap_mode = snapshot.wrap_mode;
+    // The spawner's still reading the arena's size, so the new one goes in at
+    // the end of the frame.
+    commands.insert_resource(snapshot.arena);
+    *run.shape = snapshot.shape;
+    *run.effective = EffectiveArena::from(&snapshot.arena);
+    **run.score = snapshot.score;
+    **run.lives = snapshot.lives;
+    *run.tick = MovementTick {
+        count: snapshot.tick,
+        elapsed: Duration::from_secs_f32(snapshot.elapsed),
+        ..default()
+    };
+    *run.movement_timer = MovementTimer::new(snapshot.difficulty);
+    run.movement_timer
+        .set_duration(Duration::from_secs_f32(snapshot.movement_interval));
+    run.death_flash.cancel();
+
+    let mut freeze = Timer::from_seconds(FREEZE_SECONDS, false);
+    freeze.tick(freeze.duration() - Duration::from_secs_f32(snapshot.frozen));
+    *run.freeze = FreezeTimer(freeze);
+    // The freeze system might have seen it start already, and the fresh timer
+    // has to be held still regardless.
+    if !run.freeze.finished() {
+        run.movement_timer.pause();
+    }
+
+    for snake in &snapshot.snakes {
+        spawner.spawn_snake_at(
+            &mut commands,
+            snake.player,
+            snake.direction,
+            &snake.segments,
+        );
+    }
+
+    let palette = spawner.theme.palette();
+    for food in snapshot.food {
+        match food.kind {
+            FoodKind::Big => spawn_big_food(&mut commands, food.position, &palette),
+            FoodKind::Bonus => {
+                let mut timer = Timer::from_seconds(BONUS_FOOD_LIFETIME, false);
+                timer.set_elapsed(Duration::from_secs_f32(food.elapsed));
+                spawn_food(&mut commands, food.position, food.kind, &palette)
+                    .insert(BonusFood(timer));
+            }
+            kind => {
+                spawn_food(&mut commands, food.position, kind, &palette);
+            }
+        }
+    }
+
+    resume.paused();
+    info!("Loaded the game from {}", SNAPSHOT_PATH);
+}
+
+#[derive(Deref, DerefMut)]
+struct Lives(u32);
+
+impl Default for Lives {
+    fn default() -> Self {
+        Self(STARTING_LIVES)
+    }
+}
+
+#[derive(Component)]
+struct LivesText;
+
+fn lives_text(
+    lives: Res<Lives>,
+    game_mode: Res<GameMode>,
+    mut texts: Query<&mut Text, With<LivesText>>,
+) {
+    for mut text in &mut texts {
+        text.sections[0].value = match *game_mode {
+            GameMode::Single => format!("Lives: {}", **lives),
+            GameMode::Versus => String::new(),
+        };
+    }
+}
+
+// How long the current run has been going, not counting pauses or countdowns.
+#[derive(Default, Deref, DerefMut)]
+struct PlayTime(Stopwatch);
+
+#[derive(Component)]
+struct PlayTimeText;
+
+/// A read-only summary of how the current run is going, for anything outside
+/// the game that wants to keep an eye on it. Brought up to date every frame.
+///
+/// The snake is player one's, and there isn't one on the menu.
+#[derive(Default, Clone, PartialEq, Debug)]
+pub struct GameStats {
+    pub score: usize,
+    pub high_score: usize,
+    /// How many segments long the snake is, head included.
+    pub length: usize,
+    /// Which way the snake last moved, if there is one.
+    pub direction: Option<Direction>,
+    /// How long the run has been going, not counting pauses or countdowns.
+    pub play_time: Duration,
+    /// Whether there's a snake that hasn't died.
+    pub alive: bool,
+}
+
+// Runs after everything else in a frame has had its say, so the stats match
+// what's drawn.
+fn game_stats(
+    game_state: Res<GameState>,
+    score: Res<Score>,
+    high_score: Res<HighScore>,
+    play_time: Res<PlayTime>,
+    snakes: Query<(&SnakeHead, &SnakeSegments)>,
+    mut stats: ResMut<GameStats>,
+) {
+    let snake = snakes
+        .iter()
+        .find(|(head, _)| head.player == 0)
+        .filter(|_| *game_state != GameState::Menu);
+
+    let latest = GameStats {
+        score: **score,
+        high_score: **high_score,
+        length: snake.map_or(0, |(_, segments)| segments.len()),
+        direction: snake.map(|(head, _)| head.direction),
+        play_time: play_time.elapsed(),
+        alive: snake.is_some() && *game_state != GameState::GameOver,
+    };
+
+    // Only touching it when something's different leaves change detection
+    // meaning something.
+    if *stats != latest {
+        *stats = latest;
+    }
+}
+
+fn play_time(
+    time: Res<Time>,
+    game_state: Res<GameState>,
+    countdown: Res<Countdown>,
+    mut play_time: ResMut<PlayTime>,
+) {
+    if *game_state == GameState::Running && countdown.finished() {
+        play_time.tick(time.delta());
+    }
+}
+
+fn play_time_text(play_time: Res<PlayTime>, mut texts: Query<&mut Text, With<PlayTimeText>>) {
+    if !play_time.is_changed() {
+        return;
+    }
+
+    let seconds = play_time.elapsed().as_secs();
+    for mut text in &mut texts {
+        text.sections[0].value = format!("{:02}:{:02}", seconds / 60, seconds % 60);
+    }
+}
+
+#[derive(Default, Deref, DerefMut)]
+struct HighScore(usize);
+
+fn load_high_score(mut high_score: ResMut<HighScore>) {
+    let value = match storage().read(HIGH_SCORE_PATH) {
+        Ok(contents) => contents.trim().parse().unwrap_or_else(|err| {
+            warn!(
+                "Couldn't parse {}, resetting high score: {}",
+                HIGH_SCORE_PATH, err
+            );
+            0
+        }),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            save_high_score(0);
+            0
+        }
+        Err(err) => {
+            warn!(
+                "Couldn't read {}, resetting high score: {}",
+                HIGH_SCORE_PATH, err
+            );
+            0
+        }
+    };
+
+    *high_score = HighScore(value);
+}
+
+// Returns whether the score set a new record.
+fn record_high_score(game_mode: GameMode, score: &Score, high_score: &mut HighScore) -> bool {
+    // Only touch the filesystem when the record actually changes.
+    if game_mode == GameMode::Single && **score > **high_score {
+        **high_score = **score;
+        save_high_score(**high_score);
+        true
+    } else {
+        false
+    }
+}
+
+// A run everyone gets to play the same on a given day, seeded by the date, so
+// scores can be compared. It's always on the default settings, and has a best
+// score of its own rather than counting towards the high score.
+#[derive(Default)]
+struct DailyChallenge {
+    // The day being played, as YYYYMMDD, while it's switched on.
+    date: Option<u32>,
+    // Whether the current run is the daily challenge.
+    active: bool,
+    best: DailyBest,
+}
+
+#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+struct DailyBest {
+    date: u32,
+    score: usize,
+}
+
+impl DailyChallenge {
+    fn seed(&self) -> Option<u64> {
+        self.date.filter(|_| self.active).map(u64::from)
+    }
+
+    // Yesterday's best doesn't count for today.
+    fn best_today(&self) -> usize {
+        match self.date {
+            Some(date) if date == self.best.date => self.best.score,
+            _ => 0,
+        }
+    }
+
+    fn record(&mut self, score: usize) {
+        let date = match self.date {
+            Some(date) if score > self.best_today() => date,
+            _ => return,
+        };
+
+        self.best = DailyBest { date, score };
+        let result = ron::to_string(&self.best)
+            .map_err(|err| err.to_string())
+            .and_then(|contents| {
+                storage()
+                    .write(DAILY_BEST_PATH, &contents)
+                    .map_err(|err| err.to_string())
+            });
+
+        if let Err(err) = result {
+            warn!("Couldn't save daily best to {}: {}", DAILY_BEST_PATH, err);
+        }
+    }
+}
+
+fn load_daily_best(mut daily: ResMut<DailyChallenge>) {
+    let contents = match storage().read(DAILY_BEST_PATH) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return,
+        Err(err) => {
+            warn!("Couldn't read {}: {}", DAILY_BEST_PATH, err);
+            return;
+        }
+    };
+
+    match ron::from_str::<DailyBest>(&contents) {
+        Ok(best) => daily.best = best,
+        Err(err) => warn!("Couldn't parse {}: {}", DAILY_BEST_PATH, err),
+    }
+}
+
+// Today's date in UTC, as YYYYMMDD, so it's the same day for everyone at once.
+fn today() -> u32 {
+    // Howard Hinnant's days-to-civil algorithm, with years starting in March
+    // so the leap day comes last.
+    let z = unix_days() + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year * 10_000 + month * 100 + day) as u32
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn unix_days() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() as i64 / 86_400)
+}
+
+// There's no system clock to read on the web, but there's the browser's.
+#[cfg(target_arch = "wasm32")]
+fn unix_days() -> i64 {
+    (js_sys::Date::now() / 86_400_000.0).floor() as i64
+}
+
+fn format_date(date: u32) -> String {
+    format!(
+        "{}-{:02}-{:02}",
+        date / 10_000,
+        date / 100 % 100,
+        date % 100
+    )
+}
+
+#[derive(Component)]
+struct DailyText;
+
+#[derive(Component)]
+struct DailyIndicator;
+
+// Picks up the date when it's switched on, so a session that runs past
+// midnight carries on with the day it started on.
+fn toggle_daily_challenge(
+    keyboard: Res<Input<KeyCode>>,
+    game_state: Res<GameState>,
+    mut daily: ResMut<DailyChallenge>,
+) {
+    if *game_state == GameState::Menu && keyboard.just_pressed(KeyCode::Y) {
+        daily.date = match daily.date {
+            Some(_) => None,
+            None => Some(today()),
+        };
+    }
+}
+
+fn daily_challenge_text(
+    game_state: Res<GameState>,
+    daily: Res<DailyChallenge>,
+    mut texts: Query<&mut Text, (With<DailyText>, Without<DailyIndicator>)>,
+    mut indicators: Query<(&mut Text, &mut Visibility), With<DailyIndicator>>,
+) {
+    if daily.is_changed() {
+        for mut text in &mut texts {
+            text.sections[0].value = match daily.date {
+                Some(date) => format!(
+                    "Daily: {}, best {} (Y)",
+                    format_date(date),
+                    daily.best_today()
+                ),
+                None => "Daily: Off (Y)".to_string(),
+            };
+        }
+    }
+
+    for (mut text, mut visibility) in &mut indicators {
+        visibility.is_visible = daily.active && *game_state != GameState::Menu;
+        if let Some(date) = daily.date.filter(|_| daily.is_changed()) {
+            text.sections[0].value = format!("DAILY {}, seed {}", format_date(date), date);
+        }
+    }
+}
+
+fn save_high_score(high_score: usize) {
+    if let Err(err) = storage().write(HIGH_SCORE_PATH, &high_score.to_string()) {
+        warn!("Couldn't save high score to {}: {}", HIGH_SCORE_PATH, err);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum Achievement {
+    NoWrapStreak,
+    LongSnake,
+}
+
+impl Achievement {
+    fn name(&self) -> String {
+        match self {
+            Self::NoWrapStreak => format!("Ate {} in a row without wrapping", NO_WRAP_STREAK),
+            Self::LongSnake => format!("Reached length {}", LONG_SNAKE_LENGTH),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Achievements {
+    unlocked: HashSet<Achievement>,
+    // Food eaten since the run started or the snake last wrapped.
+    streak: u32,
+}
+
+#[derive(Component)]
+struct AchievementToast(Timer);
+
+// Only runs that are actually being played count, so neither the demo nor a
+// replay can unlock anything. It's all driven off events and changed snakes,
+// so there's nothing to do on a tick where nothing happened.
+fn achievements(
+    mut toasts: AchievementToasts,
+    game_state: Res<GameState>,
+    game_mode: Res<GameMode>,
+    replay_mode: Res<ReplayMode>,
+    mut events: StreakEvents,
+    mut achievements: ResMut<Achievements>,
+    snakes: Query<(&SnakeHead, &SnakeSegments), Changed<SnakeSegments>>,
+) {
+    if events.started_over() {
+        achievements.streak = 0;
+    }
+
+    let wrapped = events.wrapped();
+    let eaten = events.eaten();
+
+    if *game_state != GameState::Running
+        || *game_mode != GameMode::Single
+        || *replay_mode != ReplayMode::Record
+    {
+        return;
+    }
+
+    // A wrap always comes before whatever the head ate where it came back in.
+    if wrapped {
+        achievements.streak = 0;
+    }
+    achievements.streak += eaten;
+
+    let mut earned = Vec::new();
+    if achievements.streak >= NO_WRAP_STREAK {
+        earned.push(Achievement::NoWrapStreak);
+    }
+    if snakes
+        .iter()
+        .any(|(head, segments)| head.player == 0 && segments.len() >= LONG_SNAKE_LENGTH)
+    {
+        earned.push(Achievement::LongSnake);
+    }
+
+    let earned = earned
+        .into_iter()
+        .filter(|achievement| achievements.unlocked.insert(*achievement))
+        .collect::<Vec<_>>();
+    for achievement in &earned {
+        info!("Unlocked achievement: {}", achievement.name());
+    }
+    toasts.show(&earned);
+}
+
+// What the no-wrap streak goes by.
+#[derive(SystemParam)]
+struct StreakEvents<'w, 's> {
+    new_run_reader: EventReader<'w, 's, NewRunEvent>,
+    game_over_reader: EventReader<'w, 's, GameOverEvent>,
+    growth_reader: EventReader<'w, 's, GrowthEvent>,
+    wrap_reader: EventReader<'w, 's, WrapEvent>,
+}
+
+impl StreakEvents<'_, '_> {
+    // Whether there's been a new run or a life lost, either of which ends the
+    // streak.
+    fn started_over(&mut self) -> bool {
+        let new_run = self.new_run_reader.iter().count() > 0;
+        let died = self.game_over_reader.iter().count() > 0;
+        new_run || died
+    }
+
+    fn wrapped(&mut self) -> bool {
+        self.wrap_reader.iter().count() > 0
+    }
+
+    // How much player one's eaten.
+    fn eaten(&mut self) -> u32 {
+        self.growth_reader
+            .iter()
+            .filter(|growth| growth.player == 0)
+            .count() as u32
+    }
+}
+
+// Pops up a note of each achievement as it's unlocked, stacked above any
+// that are still up.
+#[derive(SystemParam)]
+struct AchievementToasts<'w, 's> {
+    commands: Commands<'w, 's>,
+    asset_server: Res<'w, AssetServer>,
+    shown: Query<'w, 's, (), With<AchievementToast>>,
+}
+
+impl AchievementToasts<'_, '_> {
+    fn show(&mut self, achievements: &[Achievement]) {
+        if achievements.is_empty() {
+            return;
+        }
+
+        let font = self.asset_server.load(FONT_PATH);
+        let shown = self.shown.iter().count();
+
+        for (i, achievement) in achievements.iter().enumerate() {
+            self.commands
+                .spawn_bundle(
+                    TextBundle::from_section(
+                        format!("Achievement: {}", achievement.name()),
+                        TextStyle {
+                            font: font.clone(),
+                            color: Color::WHITE,
+                            font_size: 12.0,
+                        },
+                    )
+                    .with_style(Style {
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            bottom: Val::Px(30.0 + 20.0 * (shown + i) as f32),
+                            left: Val::Px(5.0),
+                            ..default()
+                        },
+                        ..default()
+                    }),
+                )
+                .insert(AchievementToast(Timer::from_seconds(
+                    ACHIEVEMENT_TOAST_SECONDS,
+                    false,
+                )));
+        }
+    }
+}
+
+fn achievement_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut toasts: Query<(Entity, &mut AchievementToast, &mut Text)>,
+) {
+    for (entity, mut toast, mut text) in &mut toasts {
+        if toast.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        // Holds steady for most of its time, then fades over the last second.
+        let alpha = (toast.0.duration().as_secs_f32() - toast.0.elapsed_secs()).min(1.0);
+        text.sections[0].style.color.set_a(alpha);
+    }
+}
+
+fn load_achievements(mut achievements: ResMut<Achievements>) {
+    let contents = match storage().read(ACHIEVEMENTS_PATH) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return,
+        Err(err) => {
+            warn!("Couldn't read {}: {}", ACHIEVEMENTS_PATH, err);
+            return;
+        }
+    };
+
+    match ron::from_str::<HashSet<Achievement>>(&contents) {
+        Ok(unlocked) => achievements.unlocked = unlocked,
+        Err(err) => warn!("Couldn't parse {}: {}", ACHIEVEMENTS_PATH, err),
+    }
+}
+
+fn save_achievements(achievements: Res<Achievements>, mut saved: Local<Option<usize>>) {
+    // Achievements are only ever added to, so a change in how many there are
+    // is all there is to look for. The first run only sees whatever was
+    // loaded on startup, which doesn't need writing back out.
+    let unlocked = achievements.unlocked.len();
+    if saved
+        .replace(unlocked)
+        .is_none_or(|saved| saved == unlocked)
+    {
+        return;
+    }
+
+    let result = ron::to_string(&achievements.unlocked)
+        .map_err(|err| err.to_string())
+        .and_then(|contents| {
+            storage()
+                .write(ACHIEVEMENTS_PATH, &contents)
+                .map_err(|err| err.to_string())
+        });
+
+    if let Err(err) = result {
+        warn!(
+            "Couldn't save achievements to {}: {}",
+            ACHIEVEMENTS_PATH, err
+        );
+    }
+}
+
+// Everything the player can change that should stick around between launches.
+// Each setting lives in its own resource while the game runs, and this is just
+// how they're stored on disk.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct Settings {
+    wrap_mode: WrapMode,
+    theme: Theme,
+    volume: f32,
+    grid_visible: bool,
+    key_bindings: KeyBindings,
+    particles_enabled: bool,
+    screen_shake: bool,
+    screen_shake_intensity: f32,
+    smooth_movement: bool,
+    pre_tick: bool,
+    colorblind_mode: bool,
+    diagonal_policy: DiagonalPolicy,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            wrap_mode: WrapMode::default(),
+            theme: Theme::default(),
+            volume: *Volume::default(),
+            grid_visible: *GridVisible::default(),
+            key_bindings: KeyBindings::default(),
+            particles_enabled: *ParticlesEnabled::default(),
+            screen_shake: ScreenShake::default().enabled,
+            screen_shake_intensity: ScreenShake::default().intensity,
+            smooth_movement: *SmoothMovement::default(),
+            pre_tick: *PreTick::default(),
+            colorblind_mode: *ColorblindMode::default(),
+            diagonal_policy: DiagonalPolicy::default(),
+        }
+    }
+}
+
+// The resources each setting lives in while the game runs.
+#[derive(SystemParam)]
+struct SettingsResources<'w, 's> {
+    wrap_mode: ResMut<'w, WrapMode>,
+    theme: ResMut<'w, Theme>,
+    volume: ResMut<'w, Volume>,
+    grid_visible: ResMut<'w, GridVisible>,
+    key_bindings: ResMut<'w, KeyBindings>,
+    particles_enabled: ResMut<'w, ParticlesEnabled>,
+    screen_shake: ResMut<'w, ScreenShake>,
+    smooth_movement: ResMut<'w, SmoothMovement>,
+    pre_tick: ResMut<'w, PreTick>,
+    colorblind_mode: ResMut<'w, ColorblindMode>,
+    diagonal_policy: ResMut<'w, DiagonalPolicy>,
+    #[system_param(ignore)]
+    _marker: PhantomData<&'s ()>,
+}
+
+impl SettingsResources<'_, '_> {
+    fn get(&self) -> Settings {
+        Settings {
+            wrap_mode: *self.wrap_mode,
+            theme: *self.theme,
+            volume: **self.volume,
+            grid_visible: **self.grid_visible,
+            key_bindings: self.key_bindings.clone(),
+            particles_enabled: **self.particles_enabled,
+            screen_shake: self.screen_shake.enabled,
+            screen_shake_intensity: self.screen_shake.intensity,
+            smooth_movement: **self.smooth_movement,
+            pre_tick: **self.pre_tick,
+            colorblind_mode: **self.colorblind_mode,
+            diagonal_policy: *self.diagonal_policy,
+        }
+    }
+
+    fn set(&mut self, settings: Settings) {
+        *self.wrap_mode = settings.wrap_mode;
+        *self.theme = settings.theme;
+        **self.volume = settings.volume;
+        **self.grid_visible = settings.grid_visible;
+        *self.key_bindings = settings.key_bindings;
+        **self.particles_enabled = settings.particles_enabled;
+        self.screen_shake.enabled = settings.screen_shake;
+        self.screen_shake.intensity = settings.screen_shake_intensity;
+        **self.smooth_movement = settings.smooth_movement;
+        **self.pre_tick = settings.pre_tick;
+        **self.colorblind_mode = settings.colorblind_mode;
+        *self.diagonal_policy = settings.diagonal_policy;
+    }
+}
+
+// Without a settings file everything keeps the values the plugin started it
+// with.
+fn load_settings(mut resources: SettingsResources) {
+    let contents = match storage().read(SETTINGS_PATH) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return,
+        Err(err) => {
+            warn!("Couldn't read {}, using defaults: {}", SETTINGS_PATH, err);
+            return;
+        }
+    };
+
+    let settings = ron::from_str::<Settings>(&contents).unwrap_or_else(|err| {
+        warn!("Couldn't parse {}, using defaults: {}", SETTINGS_PATH, err);
+        Settings::default()
+    });
+
+    resources.set(settings);
+}
+
+// Writes the settings back out whenever they come out different from the last
+// time they were saved.
+fn save_settings(
+    resources: SettingsResources,
+    player_options: Res<PlayerOptions>,
+    mut saved: Local<Option<Settings>>,
+) {
+    let mut settings = resources.get();
+    // A replay or the daily challenge only borrows the wrap mode, so it's the
+    // player's own that gets saved.
+    if let Some(player) = &**player_options {
+        settings.wrap_mode = player.wrap_mode;
+    }
+    // The first run only sees whatever was loaded on startup, which doesn't
+    // need writing back out.
+    if saved.as_ref() == Some(&settings) {
+        return;
+    }
+
+    if saved.is_some() {
+        match ron::to_string(&settings) {
+            Ok(contents) => {
+                if let Err(err) = storage().write(SETTINGS_PATH, &contents) {
+                    warn!("Couldn't save settings to {}: {}", SETTINGS_PATH, err);
+                }
+            }
+            Err(err) => warn!("Couldn't serialize settings: {}", err),
+        }
+    }
+
+    *saved = Some(settings);
+}
+
+/// The game without a window, stepped a movement tick at a time, so tests can
+/// play exact sequences of turns and check where everything ends up.
+///
+/// The same seed and the same turns always play out the same way, and nothing
+/// is loaded from or saved to disk.
+pub struct Simulation {
+    app: App,
+}
+
+impl Simulation {
+    /// Starts a single player run, ready for its first move. Its seed comes
+    /// from `seed`, unless `SNAKE_SEED` is set to pin it.
+    pub fn new(seed: u64) -> Self {
+        // Seeded before anything gets to draw on it, the run's own seed
+        // included.
+        let mut app = Self::build();
+        app.insert_resource(GameRng(StdRng::seed_from_u64(seed)));
+        app.update();
+        Self::press_key(&mut app, KeyCode::Space);
+
+        // Playing it back as a run without any recorded turns keeps it from
+        // counting towards the high score, and turns still come in through
+        // `turn`.
+        *app.world.resource_mut::<ReplayMode>() = ReplayMode::Playback {
+            next_input: 0,
+            next_panic: 0,
+        };
+
+        Self { app }
+    }
+
+    /// Starts playing back the best run on record, if there is one.
+    pub fn best_run() -> Option<Self> {
+        let app = Self::start(KeyCode::P);
+        (*app.world.resource::<GameState>() == GameState::Running).then(|| Self { app })
+    }
+
+    /// Starts a day's daily challenge, with its date given as YYYYMMDD.
+    pub fn daily(date: u32) -> Self {
+        let mut app = Self::headless();
+        app.world.resource_mut::<DailyChallenge>().date = Some(date);
+        Self::press_key(&mut app, KeyCode::Space);
+        Self { app }
+    }
+
+    // Sets up the game and presses a key on the menu like the player would.
+    fn start(key: KeyCode) -> App {
+        let mut app = Self::headless();
+        Self::press_key(&mut app, key);
+        app
+    }
+
+    // The game on its menu, with nothing but what it needs to run.
+    fn headless() -> App {
+        let mut app = Self::build();
+        // The first update is just startup, after which the menu's ready.
+        app.update();
+        app
+    }
+
+    // The same, before its first update.
+    fn build() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(bevy::asset::AssetPlugin)
+            .add_plugin(bevy::input::InputPlugin)
+            .add_plugin(bevy::diagnostic::DiagnosticsPlugin)
+            .init_resource::<Windows>()
+            .init_resource::<Audio>()
+            .add_plugin(SnakePlugin {
+                window_size: None,
+                persist: false,
+                death_flash: 0.0,
+                ..default()
+            });
+        app
+    }
+
+    // Presses a key and lets go of it again, a frame later.
+    fn press_key(app: &mut App, key: KeyCode) {
+        for state in [
+            bevy::input::ButtonState::Pressed,
+            bevy::input::ButtonState::Released,
+        ] {
+            app.world
+                .resource_mut::<Events<bevy::input::keyboard::KeyboardInput>>()
+                .send(bevy::input::keyboard::KeyboardInput {
+                    scan_code: 0,
+                    key_code: Some(key),
+                    state,
+                });
+            app.update();
+        }
+    }
+
+    /// Presses a key for a frame, without moving anything on a tick.
+    pub fn press(&mut self, key: KeyCode) {
+        Self::press_key(&mut self.app, key);
+    }
+
+    /// Queues up a turn for a player, same as pressing a key would.
+    pub fn turn(&mut self, player: usize, direction: Direction) {
+        let mut heads = self.app.world.query::<&mut SnakeHead>();
+        for mut head in heads.iter_mut(&mut self.app.world) {
+            if head.player == player {
+                head.queue_turn(direction);
+            }
+        }
+    }
+
+    /// Moves every snake a tile, along with everything else that follows the
+    /// snake's clock.
+    pub fn step(&mut self) {
+        // Neither waits on the wall clock here: any countdown is skipped, and
+        // the next move is always due.
+        let mut countdown = self.app.world.resource_mut::<Countdown>();
+        let remaining = countdown.duration();
+        countdown.tick(remaining);
+
+        let mut freeze = self.app.world.resource_mut::<FreezeTimer>();
+        let remaining = freeze.duration();
+        freeze.tick(remaining);
+
+        let mut movement_timer = self.app.world.resource_mut::<MovementTimer>();
+        let interval = movement_timer.duration();
+        movement_timer.set_elapsed(interval);
+
+        self.app.update();
+    }
+
+    /// Where each of a player's segments is, starting with the head. Empty if
+    /// they don't have a snake.
+    pub fn snake(&mut self, player: usize) -> Vec<IVec2> {
+        let world = &mut self.app.world;
+        let segments = world
+            .query::<(&SnakeHead, &SnakeSegments)>()
+            .iter(world)
+            .find(|(head, _)| head.player == player)
+            .map(|(_, segments)| segments.to_vec())
+            .unwrap_or_default();
+
+        segments
+            .iter()
+            .filter_map(|segment| world.get::<Position>(*segment))
+            .map(|position| IVec2::new(position.x, position.y))
+            .collect()
+    }
+
+    /// Where every piece of food on the board is.
+    pub fn food(&mut self) -> Vec<IVec2> {
+        let world = &mut self.app.world;
+        world
+            .query_filtered::<&Position, With<Food>>()
+            .iter(world)
+            .map(|position| IVec2::new(position.x, position.y))
+            .collect()
+    }
+
+    pub fn score(&self) -> usize {
+        **self.app.world.resource::<Score>()
+    }
+
+    /// The same summary of the run that's kept up to date for a real game.
+    pub fn stats(&self) -> GameStats {
+        self.app.world.resource::<GameStats>().clone()
+    }
+
+    /// How far through the current movement tick the game is.
+    pub fn tick_progress(&self) -> f32 {
+        self.app.world.resource::<TickProgress>().fraction()
+    }
+
+    /// Whether the run has ended, whether it was won or lost.
+    pub fn is_over(&self) -> bool {
+        matches!(
+            *self.app.world.resource::<GameState>(),
+            GameState::GameOver | GameState::Won
+        )
+    }
+
+    /// The app underneath, for anything the rest of this doesn't cover.
+    pub fn app(&mut self) -> &mut App {
+        &mut self.app
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // How much plain food there is, leaving out everything special.
+    fn normal_food(sim: &mut Simulation) -> usize {
+        let world = &mut sim.app().world;
+        world
+            .query_filtered::<&FoodKind, With<Food>>()
+            .iter(world)
+            .filter(|kind| **kind == FoodKind::Normal)
+            .count()
+    }
+
+    #[test]
+    fn snapshots_from_outside_the_game_are_checked() {
+        let snapshot = |player, head: (i32, i32), food: (i32, i32)| GameSnapshot {
+            version: SNAPSHOT_VERSION,
+            game_mode: GameMode::Single,
+            difficulty: Difficulty::Normal,
+            wrap_mode: WrapMode::default(),
+            arena: ArenaSize::default(),
+            shape: ArenaShape::default(),
+            score: 0,
+            lives: STARTING_LIVES,
+            tick: 0,
+            elapsed: 0.0,
+            movement_interval: Difficulty::Normal.base_interval(),
+            frozen: 0.0,
+            snakes: vec![SnakeSnapshot {
+                player,
+                direction: Direction::Up,
+                segments: vec![Position {
+                    x: head.0,
+                    y: head.1,
+                }],
+            }],
+            food: vec![FoodSnapshot {
+                position: Position {
+                    x: food.0,
+                    y: food.1,
+                },
+                kind: FoodKind::Big,
+                elapsed: 0.0,
+            }],
+        };
+
+        assert!(snapshot(0, (3, 3), (5, 5)).problem().is_none());
+        assert!(snapshot(2, (3, 3), (5, 5)).problem().is_some());
+        assert!(snapshot(0, (3, 10), (5, 5)).problem().is_some());
+        assert!(snapshot(0, (3, 3), (9, 5)).problem().is_some());
+
+        let mut frozen = snapshot(0, (3, 3), (5, 5));
+        frozen.frozen = FREEZE_SECONDS * 2.0;
+        assert!(frozen.problem().is_some());
+
+        let mut timeless = snapshot(0, (3, 3), (5, 5));
+        timeless.elapsed = -1.0;
+        assert!(timeless.problem().is_some());
+    }
+
+    #[test]
+    fn food_only_spawns_on_free_tiles() {
+        let size = ArenaSize {
+            width: 2,
+            height: 2,
+        };
+        let arena = EffectiveArena::from(&size);
+        let mask = ArenaMask::new(ArenaShape::Full, &size);
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut occupied = HashSet::new();
+
+        // Fill the arena up a tile at a time, as a snake growing would.
+        for _ in 0..arena.area() {
+            let position = random_free_position(&arena, &mask, &occupied, &mut rng)
+                .expect("there's still a free tile");
+            assert!(arena.contains(position));
+            assert!(occupied.insert(position));
+        }
+
+        assert!(random_free_position(&arena, &mask, &occupied, &mut rng).is_none());
+    }
+
+    #[test]
+    fn only_one_piece_of_food_at_a_time() {
+        let mut sim = Simulation::new(4);
+        sim.turn(0, Direction::Up);
+
+        for _ in 0..100 {
+            sim.step();
+            assert!(normal_food(&mut sim) <= 1);
+        }
+        assert_eq!(normal_food(&mut sim), 1);
+    }
+
+    // Each edge of a 10x10 arena, with the tile just past it and the one on
+    // the far side that wrapping leads to.
+    const EDGES: [((i32, i32), (i32, i32)); 4] = [
+        ((-1, 4), (9, 4)),
+        ((10, 4), (0, 4)),
+        ((4, -1), (4, 9)),
+        ((4, 10), (4, 0)),
+    ];
+
+    #[test]
+    fn wrapping_leads_round_to_the_far_edge() {
+        let arena = EffectiveArena::from(&ArenaSize::default());
+        let wrap_mode = WrapMode {
+            x: Edge::Wrap,
+            y: Edge::Wrap,
+        };
+
+        for ((x, y), (wrapped_x, wrapped_y)) in EDGES {
+            let wrapped = wrap_mode.apply(Position { x, y }, &arena);
+            assert!(
+                wrapped
+                    == Some(Position {
+                        x: wrapped_x,
+                        y: wrapped_y,
+                    })
+            );
+        }
+    }
+
+    #[test]
+    fn walls_stop_the_head_at_every_edge() {
+        let arena = EffectiveArena::from(&ArenaSize::default());
+        let wrap_mode = WrapMode {
+            x: Edge::Wall,
+            y: Edge::Wall,
+        };
+
+        for ((x, y), _) in EDGES {
+            assert!(wrap_mode.apply(Position { x, y }, &arena).is_none());
+        }
+        assert!(wrap_mode.apply(Position { x: 0, y: 9 }, &arena) == Some(Position { x: 0, y: 9 }));
+    }
+
+    #[test]
+    fn growing_speeds_the_snake_up() {
+        let start = movement_interval(0, Difficulty::Normal);
+        assert_eq!(start, Difficulty::Normal.base_interval());
+        assert!(movement_interval(5, Difficulty::Normal) < start);
+        assert_eq!(
+            movement_interval(1000, Difficulty::Normal),
+            MIN_MOVEMENT_INTERVAL
+        );
+    }
+
+    #[test]
+    fn wasd_steers_like_the_arrow_keys() {
+        let bindings = KeyBindings::default();
+        let mut head = SnakeHead::new(0, Direction::Up);
+        head.started = true;
+
+        let mut keyboard = Input::default();
+        keyboard.press(KeyCode::A);
+        let turn = keyboard_direction(&keyboard, &bindings, DiagonalPolicy::default(), None, &head);
+        assert_eq!(turn, Some(Direction::Left));
+
+        // W is still no way to turn back on itself.
+        head.direction = Direction::Down;
+        let mut keyboard = Input::default();
+        keyboard.press(KeyCode::W);
+        let turn = keyboard_direction(&keyboard, &bindings, DiagonalPolicy::default(), None, &head);
+        assert_eq!(turn, None);
+
+        head.direction = Direction::Left;
+        let turn = keyboard_direction(&keyboard, &bindings, DiagonalPolicy::default(), None, &head);
+        assert_eq!(turn, Some(Direction::Up));
+    }
+
+    #[test]
+    fn quick_turns_between_ticks_never_reverse_the_snake() {
+        let mut sim = Simulation::new(9);
+        sim.turn(0, Direction::Up);
+        sim.step();
+        assert_eq!(sim.snake(0), vec![IVec2::new(3, 4), IVec2::new(3, 3)]);
+
+        // Left and then back the other way, both before the next tick. The
+        // second is the way the snake would be reversing by then.
+        sim.turn(0, Direction::Left);
+        sim.turn(0, Direction::Right);
+        sim.step();
+        sim.step();
+
+        assert_eq!(sim.snake(0), vec![IVec2::new(1, 4), IVec2::new(2, 4)]);
+        assert_eq!(**sim.app().world.resource::<Lives>(), STARTING_LIVES);
+    }
+
+    #[test]
+    fn scaling_centers_an_arena_wider_than_it_is_tall() {
+        // A 20x10 arena in a 500px window: tiles are 25px across and 50px high.
+        assert_eq!(convert_position(0.0, 500.0, 20.0), -237.5);
+        assert_eq!(convert_position(19.0, 500.0, 20.0), 237.5);
+        assert_eq!(convert_position(0.0, 500.0, 10.0), -225.0);
+        assert_eq!(convert_position(9.0, 500.0, 10.0), 225.0);
+    }
+
+    #[test]
+    fn custom_bindings_turn_the_snake() {
+        let bindings = KeyBindings {
+            up: vec![KeyCode::I],
+            down: vec![KeyCode::K],
+            left: vec![KeyCode::J],
+            right: vec![KeyCode::L],
+            pause: vec![KeyCode::P],
+        };
+        let mut head = SnakeHead::new(0, Direction::Up);
+        head.started = true;
+
+        let mut keyboard = Input::default();
+        keyboard.press(KeyCode::J);
+        let turn = keyboard_direction(&keyboard, &bindings, DiagonalPolicy::default(), None, &head);
+        assert_eq!(turn, Some(Direction::Left));
+
+        // The arrows don't steer anything once they're bound to nothing.
+        let mut keyboard = Input::default();
+        keyboard.press(KeyCode::Right);
+        let turn = keyboard_direction(&keyboard, &bindings, DiagonalPolicy::default(), None, &head);
+        assert_eq!(turn, None);
+    }
+
+    #[test]
+    fn running_into_a_wall_ends_the_game() {
+        let mut sim = Simulation::new(11);
+        sim.app()
+            .world
+            .spawn()
+            .insert(Wall)
+            .insert(Position { x: 3, y: 4 });
+        **sim.app().world.resource_mut::<Lives>() = 1;
+
+        sim.turn(0, Direction::Up);
+        sim.step();
+
+        assert!(sim.is_over());
+    }
+
+    #[test]
+    fn restarting_puts_the_snake_back_to_its_starting_length() {
+        let mut sim = Simulation::new(12);
+        sim.app()
+            .world
+            .spawn()
+            .insert(Food)
+            .insert(FoodKind::Normal)
+            .insert(Sprite::default())
+            .insert(Position { x: 3, y: 4 });
+
+        sim.turn(0, Direction::Up);
+        sim.step();
+        sim.step();
+        assert!(sim.snake(0).len() > 2);
+
+        sim.press(KeyCode::R);
+        assert_eq!(sim.snake(0).len(), 2);
+    }
+
+    #[test]
+    fn food_never_spawns_off_the_board() {
+        let size = ArenaSize {
+            width: 20,
+            height: 10,
+        };
+        let arena = EffectiveArena::from(&size);
+        let mask = ArenaMask::new(ArenaShape::Full, &size);
+        let mut rng = StdRng::seed_from_u64(24);
+
+        for _ in 0..10_000 {
+            let position = random_free_position(&arena, &mask, &HashSet::new(), &mut rng)
+                .expect("the board's empty");
+            assert!((0..20).contains(&position.x));
+            assert!((0..10).contains(&position.y));
+        }
+    }
+
+    #[test]
+    fn games_left_to_seed_themselves_play_out_the_same() {
+        // Left to pick its own seed, with nothing recorded so the run can't
+        // count for anything.
+        let play = || {
+            let mut app = Simulation::start(KeyCode::Space);
+            *app.world.resource_mut::<ReplayMode>() = ReplayMode::Playback {
+                next_input: 0,
+                next_panic: 0,
+            };
+            let mut sim = Simulation { app };
+            let mut food = vec![sim.food()];
+            for direction in [Direction::Up, Direction::Right, Direction::Down] {
+                sim.turn(0, direction);
+                for _ in 0..4 {
+                    sim.step();
+                    food.push(sim.food());
+                }
+            }
+            food
+        };
+
+        assert_eq!(play(), play());
+    }
+
+    #[test]
+    fn growing_before_the_first_move_stacks_on_the_tail() {
+        let mut sim = Simulation::new(37);
+        let last_tail = sim
+            .app()
+            .world
+            .query::<&LastTailPosition>()
+            .single(&sim.app().world)
+            .0;
+        assert!(last_tail.is_none());
+
+        sim.app().world.send_event(GrowthEvent {
+            player: 0,
+            segments: 1,
+        });
+        sim.app().update();
+
+        assert_eq!(
+            sim.snake(0),
+            vec![IVec2::new(3, 3), IVec2::new(3, 2), IVec2::new(3, 2)]
+        );
+    }
+
+    #[test]
+    fn the_clock_only_runs_while_the_game_does() {
+        let elapsed = |sim: &mut Simulation| sim.app().world.resource::<PlayTime>().elapsed();
+
+        let mut sim = Simulation::new(38);
+        sim.turn(0, Direction::Up);
+        sim.step();
+        let before = elapsed(&mut sim);
+        std::thread::sleep(Duration::from_millis(5));
+        sim.app().update();
+        assert!(elapsed(&mut sim) > before);
+
+        sim.press(KeyCode::Escape);
+        assert!(*sim.app().world.resource::<GameState>() == GameState::Paused);
+        let paused = elapsed(&mut sim);
+        std::thread::sleep(Duration::from_millis(5));
+        sim.app().update();
+        assert_eq!(elapsed(&mut sim), paused);
+    }
+
+    #[test]
+    fn filling_the_arena_wins_the_game() {
+        let mut sim = Simulation::new(39);
+        // Just big enough for the snake as it starts out.
+        *sim.app().world.resource_mut::<EffectiveArena>() = EffectiveArena {
+            left: 3,
+            bottom: 2,
+            width: 1,
+            height: 2,
+        };
+        // The win goes through the same events as a death, which take a
+        // frame or two to get through.
+        for _ in 0..3 {
+            sim.app().update();
+        }
+
+        assert!(*sim.app().world.resource::<GameState>() == GameState::Won);
+    }
+
+    #[test]
+    fn wrapping_brings_both_axes_back_at_once() {
+        let arena = EffectiveArena::from(&ArenaSize::default());
+        let wrapped = arena.wrap(Position { x: -3, y: -3 });
+        assert!(wrapped == Position { x: 7, y: 7 });
+    }
+
+    #[test]
+    fn stepping_into_a_portal_comes_out_of_the_other() {
+        let mut sim = Simulation::new(46);
+        for (x, y) in [(3, 4), (8, 1)] {
+            sim.app()
+                .world
+                .spawn()
+                .insert(Portal { id: 0 })
+                .insert(PortalLife {
+                    timer: Timer::from_seconds(PORTAL_LIFETIME, false),
+                    uses_left: PORTAL_USES,
+                })
+                .insert(Position { x, y });
+        }
+
+        sim.turn(0, Direction::Up);
+        sim.step();
+        assert_eq!(sim.snake(0)[0], IVec2::new(8, 1));
+
+        // And carries on the same way.
+        sim.step();
+        assert_eq!(sim.snake(0)[0], IVec2::new(8, 2));
+    }
+
+    #[test]
+    fn walls_going_up_spare_the_head_for_just_one_tick() {
+        let mut sim = Simulation::new(51);
+        **sim.app().world.resource_mut::<Lives>() = 1;
+        sim.turn(0, Direction::Up);
+        for _ in 0..6 {
+            sim.step();
+        }
+        assert_eq!(sim.snake(0)[0], IVec2::new(3, 9));
+
+        // As if B had just been pressed, with the head right at the edge.
+        *sim.app().world.resource_mut::<WrapMode>() = WrapMode {
+            x: Edge::Wall,
+            y: Edge::Wall,
+        };
+        **sim.app().world.resource_mut::<WallGrace>() = true;
+        sim.step();
+        assert_eq!(sim.snake(0)[0], IVec2::new(3, 0));
+
+        // The next edge is a wall like any other.
+        sim.turn(0, Direction::Left);
+        for _ in 0..4 {
+            sim.step();
+        }
+        assert!(sim.is_over());
+    }
+
+    #[test]
+    fn a_held_key_doesnt_drown_out_a_tap() {
+        let bindings = KeyBindings::default();
+        let mut head = SnakeHead::new(0, Direction::Up);
+        head.started = true;
+
+        let mut keyboard = Input::default();
+        keyboard.press(KeyCode::Right);
+        let turn = keyboard_direction(&keyboard, &bindings, DiagonalPolicy::default(), None, &head);
+        assert_eq!(turn, Some(Direction::Right));
+
+        // Still holding Right a frame later is no turn at all.
+        head.direction = Direction::Right;
+        keyboard.clear();
+        let turn = keyboard_direction(&keyboard, &bindings, DiagonalPolicy::default(), None, &head);
+        assert_eq!(turn, None);
+
+        keyboard.press(KeyCode::Up);
+        let turn = keyboard_direction(&keyboard, &bindings, DiagonalPolicy::default(), None, &head);
+        assert_eq!(turn, Some(Direction::Up));
+    }
+
+    #[test]
+    fn chasing_your_own_tail_is_safe() {
+        let mut sim = Simulation::new(57);
+        **sim.app().world.resource_mut::<Lives>() = 1;
+        sim.app().world.send_event(GrowthEvent {
+            player: 0,
+            segments: 2,
+        });
+        sim.app().update();
+
+        // Round in a square, so the head ends up where the tail just was.
+        for direction in [Direction::Right, Direction::Down, Direction::Left] {
+            sim.turn(0, direction);
+            sim.step();
+        }
+
+        assert!(!sim.is_over());
+        assert_eq!(
+            sim.snake(0),
+            vec![
+                IVec2::new(3, 2),
+                IVec2::new(4, 2),
+                IVec2::new(4, 3),
+                IVec2::new(3, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn simulations_play_out_by_their_seed() {
+        let play = |seed| {
+            let mut sim = Simulation::new(seed);
+            sim.turn(0, Direction::Up);
+            for _ in 0..3 {
+                sim.step();
+            }
+            let seed = sim.app().world.resource::<Replay>().seed;
+            (seed, sim.food(), sim.snake(0))
+        };
+
+        assert_eq!(play(60), play(60));
+        assert_ne!(play(60).0, play(61).0);
+    }
+
+    #[test]
+    fn a_turn_back_doesnt_cost_a_sideways_one() {
+        let bindings = KeyBindings::default();
+        let mut head = SnakeHead::new(0, Direction::Up);
+        head.started = true;
+
+        let mut keyboard = Input::default();
+        keyboard.press(KeyCode::Down);
+        keyboard.press(KeyCode::Left);
+        let turn = keyboard_direction(&keyboard, &bindings, DiagonalPolicy::default(), None, &head);
+        assert_eq!(turn, Some(Direction::Left));
+    }
+
+    #[test]
+    fn shrinking_the_sandbox_never_piles_things_up() {
+        let mut sim = Simulation::new(67);
+        **sim.app().world.resource_mut::<Sandbox>() = true;
+        let world = &mut sim.app().world;
+        let wall = world
+            .spawn()
+            .insert(Wall)
+            .insert(Position { x: 8, y: 5 })
+            .id();
+        let food = world
+            .spawn()
+            .insert(Food)
+            .insert(FoodKind::Normal)
+            .insert(Position { x: 9, y: 5 })
+            .id();
+
+        sim.press(KeyCode::Minus);
+
+        // The food would've been pulled back on top of the wall.
+        let world = &sim.app().world;
+        assert_eq!(world.resource::<ArenaSize>().width, 9);
+        assert!(world.get::<Position>(wall).copied() == Some(Position { x: 8, y: 5 }));
+        assert!(world.get_entity(food).is_none());
+    }
+
+    #[test]
+    fn wrap_modes_saved_before_axes_still_load() {
+        let load = |saved: &str| ron::from_str::<WrapMode>(saved).unwrap();
+        assert!(load("Wrap") == WrapMode::default());
+        assert!(
+            load("Walls")
+                == WrapMode {
+                    x: Edge::Wall,
+                    y: Edge::Wall
+                }
+        );
+
+        let settings = ron::from_str::<Settings>("(wrap_mode: Walls, volume: 0.5)").unwrap();
+        assert!(settings.wrap_mode.x == Edge::Wall && settings.wrap_mode.y == Edge::Wall);
+        assert_eq!(settings.volume, 0.5);
+
+        for x in [Edge::Wrap, Edge::Wall, Edge::Bounce] {
+            for y in [Edge::Wrap, Edge::Wall, Edge::Bounce] {
+                let wrap_mode = WrapMode { x, y };
+                assert!(load(&ron::to_string(&wrap_mode).unwrap()) == wrap_mode);
+            }
+        }
+    }
+
+    #[test]
+    fn each_axis_wraps_or_walls_off_on_its_own() {
+        let arena = EffectiveArena::from(&ArenaSize::default());
+        let off_the_side = Position { x: -1, y: 5 };
+        let off_the_top = Position { x: 5, y: 10 };
+
+        for x in [Edge::Wrap, Edge::Wall] {
+            for y in [Edge::Wrap, Edge::Wall] {
+                let wrap_mode = WrapMode { x, y };
+
+                let side = wrap_mode.apply(off_the_side, &arena);
+                match x {
+                    Edge::Wrap => assert!(side == Some(Position { x: 9, y: 5 })),
+                    _ => assert!(side.is_none()),
+                }
+
+                let top = wrap_mode.apply(off_the_top, &arena);
+                match y {
+                    Edge::Wrap => assert!(top == Some(Position { x: 5, y: 0 })),
+                    _ => assert!(top.is_none()),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn practice_lets_the_snake_through_itself() {
+        let play = |invincible| {
+            let mut sim = Simulation::new(72);
+            **sim.app().world.resource_mut::<Lives>() = 1;
+            **sim.app().world.resource_mut::<Invincible>() = invincible;
+            sim.app().world.send_event(GrowthEvent {
+                player: 0,
+                segments: 3,
+            });
+            sim.app().update();
+
+            // Back round onto the segments still stacked up on the tail.
+            for direction in [Direction::Right, Direction::Down, Direction::Left] {
+                sim.turn(0, direction);
+                sim.step();
+            }
+            sim.is_over()
+        };
+
+        assert!(play(false));
+        assert!(!play(true));
+    }
+
+    #[test]
+    fn losing_a_life_ends_the_no_wrap_streak() {
+        let mut sim = Simulation::new(75);
+        sim.app().world.resource_mut::<Achievements>().streak = 3;
+        sim.app()
+            .world
+            .spawn()
+            .insert(Wall)
+            .insert(Position { x: 3, y: 4 });
+
+        sim.turn(0, Direction::Up);
+        sim.step();
+        sim.app().update();
+
+        assert!(!sim.is_over());
+        assert_eq!(sim.app().world.resource::<Achievements>().streak, 0);
+    }
+
+    #[test]
+    fn pre_tick_commits_to_the_last_turn_that_wont_reverse() {
+        let mut sim = Simulation::new(77);
+        // Pre-tick leaves replays alone, so this one's recorded, not that it
+        // ever scores enough to count.
+        *sim.app().world.resource_mut::<ReplayMode>() = ReplayMode::Record;
+        **sim.app().world.resource_mut::<PreTick>() = true;
+        sim.turn(0, Direction::Up);
+        sim.step();
+
+        // Down would've been fine after Left, but by the tick the snake's
+        // still heading up. The turns are committed to at the end of a frame
+        // that doesn't tick.
+        sim.turn(0, Direction::Left);
+        sim.turn(0, Direction::Down);
+        sim.app().update();
+        sim.step();
+        sim.step();
+
+        assert_eq!(sim.snake(0)[..2], [IVec2::new(1, 4), IVec2::new(2, 4)]);
+    }
+
+    #[test]
+    fn big_food_goes_all_at_once_from_any_tile() {
+        for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let mut sim = Simulation::new(78);
+            // Whichever of its tiles is right ahead of the head.
+            let corner = Position { x: 3 - x, y: 4 - y };
+            let big_food = sim
+                .app()
+                .world
+                .spawn()
+                .insert(Food)
+                .insert(FoodKind::Big)
+                .insert(BigFood::new(corner))
+                .insert(Sprite::default())
+                .insert(corner)
+                .id();
+
+            sim.turn(0, Direction::Up);
+            sim.step();
+
+            assert!(sim.app().world.get_entity(big_food).is_none());
+            assert_eq!(sim.score(), BIG_FOOD_POINTS);
+        }
+    }
+
+    #[test]
+    fn a_gamepad_turn_after_a_key_press_still_counts() {
+        use bevy::input::gamepad::{GamepadEventRaw, GamepadEventType};
+
+        let mut sim = Simulation::new(80);
+        // Input's only listened to when there's no replay steering.
+        *sim.app().world.resource_mut::<ReplayMode>() = ReplayMode::Record;
+        sim.turn(0, Direction::Up);
+        sim.step();
+
+        let gamepad = Gamepad::new(0);
+        let send = |sim: &mut Simulation, event_type| {
+            sim.app()
+                .world
+                .send_event(GamepadEventRaw::new(gamepad, event_type));
+            sim.app().update();
+        };
+        send(&mut sim, GamepadEventType::Connected);
+
+        sim.press(KeyCode::Left);
+        send(
+            &mut sim,
+            GamepadEventType::ButtonChanged(GamepadButtonType::DPadDown, 1.0),
+        );
+        sim.step();
+        sim.step();
+
+        assert_eq!(sim.snake(0)[..2], [IVec2::new(2, 3), IVec2::new(2, 4)]);
+    }
+
+    #[test]
+    fn running_into_the_enemy_ends_the_game() {
+        let mut sim = Simulation::new(82);
+        **sim.app().world.resource_mut::<Lives>() = 1;
+        sim.app()
+            .world
+            .spawn()
+            .insert(Enemy {
+                direction: Direction::Left,
+            })
+            .insert(Position { x: 3, y: 4 });
+
+        sim.turn(0, Direction::Up);
+        sim.step();
+
+        assert!(sim.is_over());
+    }
+
+    #[test]
+    fn restarting_clears_away_everything_from_the_run() {
+        let mut sim = Simulation::new(83);
+        let world = &mut sim.app().world;
+        let leftovers = [
+            world
+                .spawn()
+                .insert(GameEntity)
+                .insert(Wall)
+                .insert(Position { x: 1, y: 1 })
+                .id(),
+            world
+                .spawn()
+                .insert(GameEntity)
+                .insert(Portal { id: 0 })
+                .insert(Position { x: 8, y: 8 })
+                .id(),
+            world
+                .spawn()
+                .insert(GameEntity)
+                .insert(Enemy {
+                    direction: Direction::Left,
+                })
+                .insert(Position { x: 5, y: 1 })
+                .id(),
+        ];
+        let old_snake = sim
+            .app()
+            .world
+            .query::<(Entity, &SnakeHead)>()
+            .single(&sim.app().world)
+            .0;
+
+        sim.press(KeyCode::R);
+
+        for entity in leftovers.into_iter().chain([old_snake]) {
+            assert!(sim.app().world.get_entity(entity).is_none());
+        }
+        assert_eq!(sim.snake(0), vec![IVec2::new(3, 3), IVec2::new(3, 2)]);
+    }
+
+    #[test]
+    fn food_is_worth_more_on_hard() {
+        for difficulty in [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard] {
+            let mut sim = Simulation::new(86);
+            *sim.app().world.resource_mut::<Difficulty>() = difficulty;
+            sim.app()
+                .world
+                .spawn()
+                .insert(Food)
+                .insert(FoodKind::Normal)
+                .insert(Sprite::default())
+                .insert(Position { x: 3, y: 4 });
+
+            sim.turn(0, Direction::Up);
+            sim.step();
+
+            assert_eq!(sim.score(), difficulty.food_value());
+        }
+        assert_eq!(Difficulty::Hard.food_value(), 3);
+    }
+
+    #[test]
+    fn stats_follow_the_run() {
+        let mut sim = Simulation::new(88);
+        **sim.app().world.resource_mut::<Lives>() = 1;
+        sim.app()
+            .world
+            .spawn()
+            .insert(Food)
+            .insert(FoodKind::Normal)
+            .insert(Sprite::default())
+            .insert(Position { x: 3, y: 4 });
+
+        sim.turn(0, Direction::Up);
+        sim.step();
+        sim.app().update();
+
+        let stats = sim.stats();
+        assert_eq!(stats.score, 1);
+        assert_eq!(stats.length, 3);
+        assert_eq!(stats.direction, Some(Direction::Up));
+        assert!(stats.alive);
+
+        sim.app()
+            .world
+            .spawn()
+            .insert(Enemy {
+                direction: Direction::Left,
+            })
+            .insert(Position { x: 4, y: 4 });
+        sim.turn(0, Direction::Right);
+        sim.step();
+        sim.app().update();
+
+        assert!(sim.is_over());
+        assert!(!sim.stats().alive);
+    }
+
+    #[test]
+    fn a_freeze_lets_go_of_the_snake_once_it_wears_off() {
+        let mut sim = Simulation::new(90);
+        *sim.app().world.resource_mut::<FreezeTimer>() =
+            FreezeTimer(Timer::from_seconds(FREEZE_SECONDS, false));
+        sim.app().update();
+        assert!(sim.app().world.resource::<MovementTimer>().paused());
+
+        // Stepping wears the freeze off before it moves anything.
+        sim.turn(0, Direction::Up);
+        sim.step();
+        sim.app().update();
+        assert!(!sim.app().world.resource::<MovementTimer>().paused());
+        assert_eq!(sim.snake(0)[0], IVec2::new(3, 4));
+    }
+
+    #[test]
+    fn up_and_right_together_go_by_the_diagonal_policy() {
+        let bindings = KeyBindings::default();
+        let mut head = SnakeHead::new(0, Direction::Right);
+        head.started = true;
+
+        let mut keyboard = Input::default();
+        keyboard.press(KeyCode::Up);
+        keyboard.press(KeyCode::Right);
+        let turn = |keyboard: &Input<KeyCode>, policy| {
+            keyboard_direction(keyboard, &bindings, policy, None, &head)
+        };
+        assert_eq!(turn(&keyboard, DiagonalPolicy::KeepDirection), None);
+        assert_eq!(
+            turn(&keyboard, DiagonalPolicy::OtherAxis),
+            Some(Direction::Up)
+        );
+
+        // Up on its own while Right's still held down from before is a turn
+        // either way.
+        keyboard.clear();
+        keyboard.release(KeyCode::Up);
+        keyboard.press(KeyCode::Up);
+        for policy in [DiagonalPolicy::KeepDirection, DiagonalPolicy::OtherAxis] {
+            assert_eq!(turn(&keyboard, policy), Some(Direction::Up));
+        }
+    }
+
+    #[test]
+    fn tick_progress_stays_below_a_whole_tick() {
+        let mut sim = Simulation::new(95);
+        sim.turn(0, Direction::Up);
+        sim.step();
+        assert!((0.0..1.0).contains(&sim.tick_progress()));
+
+        for fraction in [0.0, 0.25, 0.5, 0.75, 0.95] {
+            let mut movement_timer = sim.app().world.resource_mut::<MovementTimer>();
+            let elapsed = movement_timer.duration().mul_f32(fraction);
+            movement_timer.set_elapsed(elapsed);
+            sim.app().update();
+
+            let progress = sim.tick_progress();
+            assert!((0.0..1.0).contains(&progress), "{}", progress);
+            assert!(progress >= fraction, "{} < {}", progress, fraction);
+        }
+    }
+
+    #[test]
+    fn the_panic_button_is_no_help_on_hard() {
+        for (difficulty, head) in [
+            (Difficulty::Normal, IVec2::new(5, 5)),
+            (Difficulty::Hard, IVec2::new(3, 4)),
+        ] {
+            let mut sim = Simulation::new(96);
+            *sim.app().world.resource_mut::<ReplayMode>() = ReplayMode::Record;
+            *sim.app().world.resource_mut::<Difficulty>() = difficulty;
+            sim.turn(0, Direction::Up);
+            sim.step();
+
+            sim.press(KeyCode::Space);
+
+            assert_eq!(sim.snake(0)[0], head);
+        }
+    }
+
+    #[test]
+    fn a_short_snake_never_runs_into_its_own_neck() {
+        let mut sim = Simulation::new(98);
+        **sim.app().world.resource_mut::<Lives>() = 1;
+        sim.app()
+            .world
+            .spawn()
+            .insert(Food)
+            .insert(FoodKind::Normal)
+            .insert(Sprite::default())
+            .insert(Position { x: 3, y: 4 });
+        sim.turn(0, Direction::Up);
+        sim.step();
+        sim.app().update();
+        assert_eq!(
+            sim.snake(0),
+            vec![IVec2::new(3, 4), IVec2::new(3, 3), IVec2::new(3, 2)]
+        );
+
+        // Turning back on itself is never a move at all.
+        sim.turn(0, Direction::Down);
+        sim.step();
+        assert_eq!(sim.snake(0)[0], IVec2::new(3, 5));
+
+        // A portal leading straight back onto the neck isn't the player's
+        // doing either.
+        for (x, y) in [(4, 5), (3, 4)] {
+            sim.app()
+                .world
+                .spawn()
+                .insert(Portal { id: 0 })
+                .insert(PortalLife {
+                    timer: Timer::from_seconds(PORTAL_LIFETIME, false),
+                    uses_left: PORTAL_USES,
+                })
+                .insert(Position { x, y });
+        }
+        sim.turn(0, Direction::Right);
+        sim.step();
+        sim.app().update();
+
+        assert!(!sim.is_over());
+        assert_eq!(sim.snake(0)[0], IVec2::new(3, 4));
+    }
+
+    #[test]
+    fn bouncing_turns_the_head_along_each_edge() {
+        let bounce = WrapMode {
+            x: Edge::Bounce,
+            y: Edge::Bounce,
+        };
+        // Which way to go and for how many ticks to reach the edge, and where
+        // the head is and which way it's going once it's bounced off it.
+        let edges = [
+            (vec![(Direction::Up, 6)], IVec2::new(4, 9), Direction::Right),
+            (vec![(Direction::Left, 3)], IVec2::new(0, 4), Direction::Up),
+            (
+                vec![(Direction::Right, 6)],
+                IVec2::new(9, 2),
+                Direction::Down,
+            ),
+            (
+                vec![(Direction::Right, 1), (Direction::Down, 3)],
+                IVec2::new(3, 0),
+                Direction::Left,
+            ),
+            // Into the top left corner, where clockwise is off the top.
+            (
+                vec![(Direction::Up, 6), (Direction::Left, 3)],
+                IVec2::new(0, 8),
+                Direction::Down,
+            ),
+        ];
+
+        for (legs, head, direction) in edges {
+            let mut sim = Simulation::new(99);
+            *sim.app().world.resource_mut::<WrapMode>() = bounce;
+            for (turn, ticks) in legs {
+                sim.turn(0, turn);
+                for _ in 0..ticks {
+                    sim.step();
+                }
+            }
+            sim.step();
+
+            assert!(!sim.is_over());
+            assert_eq!(sim.snake(0)[0], head);
+            assert_eq!(sim.stats().direction, Some(direction));
+        }
+    }
+
+    #[test]
+    fn speed_ramp_speeds_up_over_time() {
+        let speed_ramp = SpeedRamp {
+            enabled: true,
+            base: Difficulty::Normal.base_interval(),
+            ..default()
+        };
+
+        let start = speed_ramp.interval(Duration::ZERO);
+        let later = speed_ramp.interval(Duration::from_secs(30));
+        assert_eq!(start, Difficulty::Normal.base_interval());
+        assert!(later < start);
+        assert_eq!(
+            speed_ramp.interval(Duration::from_secs(3600)),
+            MIN_MOVEMENT_INTERVAL
+        );
+    }
+
+    #[test]
+    fn restart_resets_the_run_but_not_the_high_score() {
+        let mut sim = Simulation::new(1);
+        sim.step();
+        let world = &mut sim.app().world;
+        **world.resource_mut::<Score>() = 5;
+        **world.resource_mut::<HighScore>() = 7;
+        **world.resource_mut::<Lives>() = 1;
+        world.resource_mut::<Combo>().multiplier = 3;
+
+        sim.press(KeyCode::R);
+
+        let world = &sim.app().world;
+        assert_eq!(**world.resource::<Score>(), 0);
+        assert_eq!(world.resource::<Combo>().multiplier, 1);
+        assert_eq!(**world.resource::<Lives>(), STARTING_LIVES);
+        assert_eq!(**world.resource::<HighScore>(), 7);
+    }
+
+    #[test]
+    fn daily_challenge_spawns_the_same_food_on_the_same_day() {
+        let food = || {
+            let mut sim = Simulation::daily(20261015);
+            (0..30)
+                .map(|_| {
+                    sim.step();
+                    let mut food = sim.food();
+                    food.sort_by_key(|position| (position.x, position.y));
+                    food
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(food(), food());
+    }
+
+    #[test]
+    fn the_speed_ramp_plays_back_the_same_however_long_it_takes() {
+        // One run goes by as fast as it can, and the other with the wall
+        // clock running on between each tick.
+        let play = |pause: Duration| {
+            let mut sim = Simulation::new(64);
+            *sim.app().world.resource_mut::<SpeedRamp>() = SpeedRamp {
+                enabled: true,
+                base: Difficulty::Normal.base_interval(),
+                per_second: 1.0,
+                ..default()
+            };
+
+            (0..60)
+                .map(|i| {
+                    if i % 7 == 0 {
+                        let turn = if i % 14 == 0 {
+                            Direction::Up
+                        } else {
+                            Direction::Right
+                        };
+                        sim.turn(0, turn);
+                    }
+                    sim.step();
+                    std::thread::sleep(pause);
+
+                    let mut food = sim.food();
+                    food.sort_by_key(|position| (position.x, position.y));
+                    (sim.snake(0), food)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(play(Duration::ZERO), play(Duration::from_millis(3)));
+    }
+
+    #[test]
+    fn a_long_snake_bounces_round_the_arena_without_folding_over() {
+        let mut sim = Simulation::new(99);
+        *sim.app().world.resource_mut::<WrapMode>() = WrapMode {
+            x: Edge::Bounce,
+            y: Edge::Bounce,
+        };
+        sim.app().world.send_event(GrowthEvent {
+            player: 0,
+            segments: 4,
+        });
+        sim.app().update();
+
+        // Up to the top and then round the edges, without any food about to
+        // stack segments up on the tail.
+        sim.turn(0, Direction::Up);
+        for tick in 0..30 {
+            let world = &mut sim.app().world;
+            let food = world
+                .query_filtered::<Entity, With<Food>>()
+                .iter(world)
+                .collect::<Vec<_>>();
+            for entity in food {
+                world.despawn(entity);
+            }
+            sim.step();
+
+            assert!(!sim.is_over());
+            // The new segments are stacked up on the tail until it's moved
+            // off all of them.
+            if tick >= 4 {
+                let snake = sim.snake(0);
+                assert_eq!(snake.len(), 6);
+                assert_eq!(snake.iter().collect::<HashSet<_>>().len(), snake.len());
+            }
+        }
+    }
+}